@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{loaders::forge::Mod, parsers};
+
+// Frames from these packages belong to the JVM, the game or the loader and
+// say nothing about which mod is at fault.
+const IGNORED_PACKAGES: [&str; 9] = [
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "com.mojang.",
+    "net.minecraft.",
+    "net.minecraftforge.",
+    "cpw.mods.",
+    "org.spongepowered.",
+];
+
+const MAX_REASONS: usize = 10;
+
+#[derive(Serialize, Clone)]
+pub(crate) struct Suspect {
+    pub(crate) mod_id: String,
+    pub(crate) display_name: Option<String>,
+    pub(crate) file: Option<String>,
+    pub(crate) score: u32,
+    pub(crate) reasons: Vec<String>,
+}
+
+pub(crate) fn analyze<T: AsRef<str>>(data: T, mods: &[Mod]) -> Vec<Suspect> {
+    let report = parsers::crash_report::parse(data);
+    let mut packages: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut suspects: HashMap<usize, Suspect> = HashMap::new();
+
+    for (index, r#mod) in mods.iter().enumerate() {
+        for package in &r#mod.packages {
+            packages.entry(package).or_default().push(index);
+        }
+    }
+
+    let mut blame = |index: usize, score: u32, reason: String| {
+        let r#mod = &mods[index];
+        let suspect = suspects.entry(index).or_insert_with(|| Suspect {
+            mod_id: r#mod.mod_id.clone(),
+            display_name: r#mod.display_name.clone(),
+            file: r#mod.file.clone(),
+            score: 0,
+            reasons: vec![],
+        });
+
+        suspect.score += score;
+
+        if suspect.reasons.len() < MAX_REASONS && !suspect.reasons.contains(&reason) {
+            suspect.reasons.push(reason);
+        }
+    };
+
+    for mod_id in &report.suspected {
+        for (index, _) in mods
+            .iter()
+            .enumerate()
+            .filter(|(_, r#mod)| &r#mod.mod_id == mod_id)
+        {
+            blame(
+                index,
+                10,
+                "named as suspected mod by the loader".to_string(),
+            );
+        }
+    }
+
+    for mixin in &report.mixins {
+        for (index, _) in mods
+            .iter()
+            .enumerate()
+            .filter(|(_, r#mod)| r#mod.mixins.contains(mixin))
+        {
+            blame(index, 2, format!("mixin {mixin} is involved"));
+        }
+    }
+
+    let mut first = true;
+
+    for frame in &report.frames {
+        if IGNORED_PACKAGES
+            .iter()
+            .any(|package| frame.class.starts_with(package))
+        {
+            continue;
+        }
+
+        let mut owners: Vec<usize> = vec![];
+
+        if let Some(jar) = &frame.jar {
+            owners = mods
+                .iter()
+                .enumerate()
+                .filter(|(_, r#mod)| r#mod.file.as_ref() == Some(jar))
+                .map(|(index, _)| index)
+                .collect();
+        }
+
+        if owners.is_empty() {
+            if let Some(module) = &frame.module {
+                owners = mods
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r#mod)| &r#mod.mod_id == module)
+                    .map(|(index, _)| index)
+                    .collect();
+            }
+        }
+
+        let mut package = frame.package();
+
+        while owners.is_empty() {
+            let Some(current) = package else {
+                break;
+            };
+
+            if let Some(indices) = packages.get(current) {
+                owners = indices.clone();
+            }
+
+            package = current.rsplit_once('.').map(|(parent, _)| parent);
+        }
+
+        let score = if first { 5 } else { 1 };
+
+        first &= owners.is_empty();
+
+        for index in owners {
+            blame(
+                index,
+                score,
+                format!("stack frame {}.{}", frame.class, frame.method),
+            );
+        }
+    }
+
+    let mut suspects: Vec<Suspect> = suspects.into_values().collect();
+
+    suspects.sort_by(|a, b| b.score.cmp(&a.score).then(a.mod_id.cmp(&b.mod_id)));
+
+    suspects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORGE_1_20_1: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/crash-reports/forge-1.20.1.txt"
+    ));
+    const FORGE_1_12_2: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/crash-reports/forge-1.12.2.txt"
+    ));
+
+    fn r#mod(mod_id: &str, file: Option<&str>, packages: &[&str], mixins: &[&str]) -> Mod {
+        let mut r#mod: Mod = toml::from_str(&format!("modId = \"{mod_id}\"")).unwrap();
+
+        r#mod.file = file.map(str::to_string);
+        r#mod.packages = packages.iter().map(|package| package.to_string()).collect();
+        r#mod.mixins = mixins.iter().map(|mixin| mixin.to_string()).collect();
+        r#mod
+    }
+
+    fn modern_mods(goblins_file: &str) -> Vec<Mod> {
+        vec![
+            r#mod(
+                "curios",
+                Some("curios-forge-5.2.0+1.20.1.jar"),
+                &["top.theillusivec4.curios", "top.theillusivec4.curios.mixin"],
+                &["curios.mixins.json"],
+            ),
+            r#mod(
+                "goblins",
+                Some(goblins_file),
+                &[
+                    "com.example.goblins",
+                    "com.example.goblins.entity",
+                    "com.example.goblins.entity.ai",
+                ],
+                &["goblins.mixins.json"],
+            ),
+        ]
+    }
+
+    #[test]
+    fn blames_suspected_mod() {
+        let suspects = analyze(FORGE_1_20_1, &modern_mods("goblins-1.4.2.jar"));
+
+        assert_eq!(suspects.len(), 2);
+        assert_eq!(suspects[0].mod_id, "goblins");
+        assert_eq!(suspects[0].file.as_deref(), Some("goblins-1.4.2.jar"));
+        assert_eq!(suspects[0].score, 21);
+        assert_eq!(
+            suspects[0].reasons,
+            vec![
+                "named as suspected mod by the loader",
+                "mixin goblins.mixins.json is involved",
+                "stack frame com.example.goblins.entity.ai.ChaseGoal.m_8037_",
+                "stack frame com.example.goblins.entity.GoblinEntity.m_8107_",
+            ]
+        );
+        assert_eq!(suspects[1].mod_id, "curios");
+        assert_eq!(suspects[1].score, 2);
+        assert_eq!(
+            suspects[1].reasons,
+            vec!["mixin curios.mixins.json is involved"]
+        );
+    }
+
+    #[test]
+    fn resolves_renamed_jar_from_packages() {
+        let suspects = analyze(FORGE_1_20_1, &modern_mods("goblins.jar"));
+
+        assert_eq!(suspects[0].mod_id, "goblins");
+        assert_eq!(suspects[0].score, 21);
+    }
+
+    #[test]
+    fn resolves_legacy_frames_from_packages() {
+        let mods = vec![
+            r#mod(
+                "corelib",
+                Some("corelib-1.12.2-2.1.0.jar"),
+                &["com.example.corelib", "com.example.corelib.graph"],
+                &[],
+            ),
+            // Only the root package, so frames have to walk up to it.
+            r#mod(
+                "pipes",
+                Some("pipes-1.12.2-3.0.4.jar"),
+                &["com.example.pipes"],
+                &[],
+            ),
+            r#mod("unrelated", None, &["com.example.other"], &[]),
+        ];
+        let suspects = analyze(FORGE_1_12_2, &mods);
+
+        assert_eq!(suspects.len(), 2);
+        assert_eq!(suspects[0].mod_id, "pipes");
+        assert_eq!(suspects[0].score, 7);
+        assert_eq!(
+            suspects[0].reasons[0],
+            "stack frame com.example.pipes.tile.TilePipe.distribute"
+        );
+        assert_eq!(suspects[1].mod_id, "corelib");
+        assert_eq!(suspects[1].score, 1);
+        assert_eq!(
+            suspects[1].reasons,
+            vec!["stack frame com.example.corelib.graph.Graph.walk"]
+        );
+    }
+
+    #[test]
+    fn ignores_game_frames() {
+        let mods = vec![r#mod("minecraft", None, &["net.minecraft.world"], &[])];
+
+        assert!(analyze(FORGE_1_12_2, &mods).is_empty());
+        assert!(analyze(FORGE_1_20_1, &[]).is_empty());
+    }
+}
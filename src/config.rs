@@ -0,0 +1,433 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use tokio::{fs, sync::mpsc, time};
+
+use crate::{
+    data::{app, ip_filter::IpFilter, throttle::ThrottlePolicy, users::User},
+    supervisor::CrashPolicy,
+};
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(toml::de::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read config file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse config file: {error}"),
+            Error::Invalid(reason) => write!(f, "invalid config file: {reason}"),
+        }
+    }
+}
+
+// Everything is optional, anything missing from the file keeps its default.
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) bind: IpAddr,
+    pub(crate) port: u16,
+    pub(crate) start_command: String,
+    pub(crate) tls: Tls,
+    pub(crate) cors: Cors,
+    pub(crate) ip_filter: IpFilter,
+    pub(crate) users: HashMap<String, User>,
+    pub(crate) limits: Limits,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Tls {
+    pub(crate) enabled: bool,
+    pub(crate) cert: Option<PathBuf>,
+    pub(crate) key: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Cors {
+    // Websites allowed to call the api from a browser, `*` for any. Without
+    // any only pages served from the panel itself can.
+    pub(crate) origins: Vec<String>,
+    // Lets browsers send cookies along, which must never be combined with `*`.
+    pub(crate) credentials: bool,
+}
+
+// Durations are in seconds.
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Limits {
+    pub(crate) stop_timeout: u64,
+    pub(crate) rcon_timeout: u64,
+    pub(crate) session_lifetime: u64,
+    pub(crate) login_attempts: usize,
+    pub(crate) login_failures: u32,
+    pub(crate) login_lockout: u64,
+    pub(crate) login_lockout_max: u64,
+    pub(crate) crash_restart: bool,
+    pub(crate) crash_backoff: u64,
+    pub(crate) crash_backoff_max: u64,
+    pub(crate) crash_retries: usize,
+    pub(crate) crash_window: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            start_command: "sh run.sh".to_string(),
+            tls: Tls::default(),
+            cors: Cors::default(),
+            ip_filter: IpFilter::default(),
+            users: HashMap::new(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            stop_timeout: 60,
+            rcon_timeout: 10,
+            session_lifetime: 43200,
+            login_attempts: 10,
+            login_failures: 5,
+            login_lockout: 30,
+            login_lockout_max: 3600,
+            crash_restart: true,
+            crash_backoff: 5,
+            crash_backoff_max: 300,
+            crash_retries: 5,
+            crash_window: 900,
+        }
+    }
+}
+
+impl Config {
+    pub(crate) async fn load(path: &Path) -> Result<Config, Error> {
+        let data = fs::read_to_string(path).await.map_err(Error::Read)?;
+
+        Config::parse(&data)
+    }
+
+    fn parse(data: &str) -> Result<Config, Error> {
+        let config: Config = toml::from_str(data).map_err(Error::Parse)?;
+
+        if config.cors.credentials && config.cors.origins.iter().any(|origin| origin == "*") {
+            return Err(Error::Invalid(
+                "cors credentials cannot be allowed for every origin",
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+impl Cors {
+    pub(crate) fn allows(&self, origin: &str) -> bool {
+        self.origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+}
+
+impl Limits {
+    pub(crate) fn throttle(&self) -> ThrottlePolicy {
+        ThrottlePolicy {
+            max_attempts: self.login_attempts,
+            max_failures: self.login_failures,
+            lockout: Duration::from_secs(self.login_lockout),
+            max_lockout: Duration::from_secs(self.login_lockout_max),
+        }
+    }
+
+    pub(crate) fn crash(&self) -> CrashPolicy {
+        CrashPolicy {
+            restart: self.crash_restart,
+            backoff: Duration::from_secs(self.crash_backoff),
+            max_backoff: Duration::from_secs(self.crash_backoff_max),
+            max_retries: self.crash_retries,
+            window: Duration::from_secs(self.crash_window),
+        }
+    }
+}
+
+// Reloads the config file whenever it changes. `overrides` puts the command
+// line flags back on top, and `extra_users` are the accounts that do not come
+// from the file.
+pub(crate) async fn watch<F>(
+    state: Arc<app::State>,
+    path: PathBuf,
+    mut current: Config,
+    extra_users: HashMap<String, User>,
+    overrides: F,
+) where
+    F: Fn(&mut Config),
+{
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let name = path.file_name().map(|name| name.to_os_string());
+
+    let mut watcher =
+        match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            // Editors tend to replace the file rather than write to it, so the
+            // folder is watched and everything else in it ignored.
+            if event.is_ok_and(|event| {
+                event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == name.as_deref())
+            }) {
+                let _ = sender.send(());
+            }
+        }) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                println!("Failed to create config watcher: {error}");
+
+                return;
+            }
+        };
+
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+
+    if let Err(error) = watcher.watch(folder, RecursiveMode::NonRecursive) {
+        println!("Failed to watch config file: {error}");
+
+        return;
+    }
+
+    while receiver.recv().await.is_some() {
+        // A save usually comes as several events, wait for them to settle.
+        time::sleep(Duration::from_millis(500)).await;
+
+        while receiver.try_recv().is_ok() {}
+
+        let mut config = match Config::load(&path).await {
+            Ok(config) => config,
+            Err(error) => {
+                println!("Keeping the previous config, {error}");
+
+                continue;
+            }
+        };
+
+        overrides(&mut config);
+
+        if config == current {
+            continue;
+        }
+
+        apply(&state, &current, &config, &extra_users);
+        current = config;
+
+        println!("Reloaded config from {}", path.display());
+    }
+}
+
+fn apply(
+    state: &app::State,
+    previous: &Config,
+    config: &Config,
+    extra_users: &HashMap<String, User>,
+) {
+    if config.bind != previous.bind
+        || config.port != previous.port
+        || config.tls != previous.tls
+        || config.cors.credentials != previous.cors.credentials
+        || config.limits.rcon_timeout != previous.limits.rcon_timeout
+    {
+        println!(
+            "Changes to bind, port, tls, cors credentials and rcon_timeout take effect after a restart"
+        );
+    }
+
+    if config.users != previous.users {
+        match reloaded_users(config, extra_users, !state.users.is_empty()) {
+            Some(users) => state.users.replace(users),
+            None => println!("Not removing every user, that would leave the panel open"),
+        }
+    }
+
+    *state.cors.write().expect("cors lock poisoned") = config.cors.clone();
+    *state.ip_filter.write().expect("ip filter lock poisoned") = config.ip_filter.clone();
+
+    state.supervisor.configure(
+        config.start_command.clone(),
+        Duration::from_secs(config.limits.stop_timeout),
+        config.limits.crash(),
+    );
+    state.throttle.configure(config.limits.throttle());
+    state
+        .sessions
+        .configure(Duration::from_secs(config.limits.session_lifetime));
+}
+
+// `None` keeps the current accounts. Without any the panel is open to
+// everyone, which a reload must not do to a panel that had some.
+fn reloaded_users(
+    config: &Config,
+    extra_users: &HashMap<String, User>,
+    has_users: bool,
+) -> Option<HashMap<String, User>> {
+    let mut users = config.users.clone();
+
+    users.extend(extra_users.clone());
+
+    if users.is_empty() && has_users {
+        return None;
+    }
+
+    Some(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::role::Role;
+
+    fn user(role: Role) -> User {
+        User {
+            password: "hash".to_string(),
+            role,
+        }
+    }
+
+    #[test]
+    fn an_empty_file_is_the_default() {
+        assert!(Config::parse("").unwrap() == Config::default());
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = Config::parse(
+            r#"
+            port = 4000
+
+            [limits]
+            stop_timeout = 5
+
+            [tls]
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        let defaults = Config::default();
+
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.bind, defaults.bind);
+        assert_eq!(config.start_command, "sh run.sh");
+        assert_eq!(config.limits.stop_timeout, 5);
+        assert_eq!(config.limits.rcon_timeout, defaults.limits.rcon_timeout);
+        assert_eq!(config.limits.crash_retries, defaults.limits.crash_retries);
+        assert!(config.tls.enabled);
+        assert!(config.tls.cert.is_none());
+        assert!(config.users.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for data in [
+            "prot = 4000",
+            "[limits]\ncrash_retry = 1",
+            "[tls]\ncertificate = \"cert.pem\"",
+            "[cors]\norigin = [\"*\"]",
+            "[ip_filter]\nalow = []",
+        ] {
+            assert!(
+                matches!(Config::parse(data), Err(Error::Parse(_))),
+                "{data}"
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_types_are_rejected() {
+        assert!(matches!(
+            Config::parse("port = \"4000\""),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            Config::parse("bind = \"localhost\""),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn credentials_are_never_sent_to_every_origin() {
+        assert!(matches!(
+            Config::parse("[cors]\norigins = [\"*\"]\ncredentials = true"),
+            Err(Error::Invalid(_))
+        ));
+        assert!(
+            Config::parse("[cors]\norigins = [\"https://example.com\"]\ncredentials = true")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn users() {
+        let config = Config::parse(
+            r#"
+            [users.bob]
+            password = "hash"
+            role = "admin"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.users["bob"] == user(Role::Admin));
+        assert!(Config::parse("[users.bob]\npassword = \"hash\"\nrole = \"owner\"").is_err());
+    }
+
+    #[test]
+    fn a_reload_does_not_remove_every_user() {
+        let empty = Config::default();
+        let mut config = Config::default();
+
+        config.users.insert("bob".to_string(), user(Role::Admin));
+
+        assert!(reloaded_users(&empty, &HashMap::new(), true).is_none());
+        // The panel was open already.
+        assert!(
+            reloaded_users(&empty, &HashMap::new(), false).is_some_and(|users| users.is_empty())
+        );
+
+        let extra = HashMap::from([("alice".to_string(), user(Role::Viewer))]);
+        let users = reloaded_users(&empty, &extra, true).expect("alice is left");
+
+        assert_eq!(users.keys().collect::<Vec<_>>(), vec!["alice"]);
+
+        let users = reloaded_users(&config, &extra, true).expect("both are left");
+
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn accounts_outside_the_file_win() {
+        let mut config = Config::default();
+
+        config.users.insert("alice".to_string(), user(Role::Admin));
+
+        let extra = HashMap::from([("alice".to_string(), user(Role::Viewer))]);
+        let users = reloaded_users(&config, &extra, true).unwrap();
+
+        assert!(users["alice"] == user(Role::Viewer));
+    }
+}
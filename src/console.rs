@@ -0,0 +1,124 @@
+use std::{collections::VecDeque, io::SeekFrom, sync::Arc};
+
+use notify::{RecursiveMode, Watcher};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::{broadcast, mpsc, Mutex},
+};
+
+use crate::data::app;
+
+const HISTORY_SIZE: usize = 500;
+
+#[derive(Clone)]
+pub(crate) struct Console {
+    lines: broadcast::Sender<String>,
+    history: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Console {
+    pub(crate) fn new() -> Console {
+        let (lines, _) = broadcast::channel(HISTORY_SIZE);
+
+        Self {
+            lines,
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(HISTORY_SIZE))),
+        }
+    }
+
+    pub(crate) async fn push(&self, line: String) {
+        let mut history = self.history.lock().await;
+
+        if history.len() == HISTORY_SIZE {
+            history.pop_front();
+        }
+
+        history.push_back(line.clone());
+
+        // Nobody listening is not an error, the line is still kept in history.
+        let _ = self.lines.send(line);
+    }
+
+    pub(crate) async fn subscribe(&self) -> (Vec<String>, broadcast::Receiver<String>) {
+        let history = self.history.lock().await;
+
+        (history.iter().cloned().collect(), self.lines.subscribe())
+    }
+}
+
+// Follows logs/latest.log for servers the panel did not start itself, the
+// output of supervised servers already arrives through their stdout.
+pub(crate) async fn tail(state: Arc<app::State>) {
+    let path = state.path.join("logs/latest.log");
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let mut watcher =
+        match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok() {
+                let _ = sender.send(());
+            }
+        }) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                println!("Failed to create log watcher: {error}");
+
+                return;
+            }
+        };
+
+    if let Err(error) = watcher.watch(&state.path.join("logs"), RecursiveMode::NonRecursive) {
+        println!("Failed to watch logs folder: {error}");
+
+        return;
+    }
+
+    let mut offset = fs::metadata(&path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let mut partial = String::new();
+
+    while receiver.recv().await.is_some() {
+        let Ok(mut file) = fs::File::open(&path).await else {
+            continue;
+        };
+        let Ok(length) = file.metadata().await.map(|metadata| metadata.len()) else {
+            continue;
+        };
+
+        // The server rotates latest.log on every start.
+        if length < offset {
+            offset = 0;
+            partial.clear();
+        }
+
+        if length == offset || file.seek(SeekFrom::Start(offset)).await.is_err() {
+            continue;
+        }
+
+        let mut data = vec![];
+
+        if file.read_to_end(&mut data).await.is_err() {
+            continue;
+        }
+
+        offset += data.len() as u64;
+        partial.push_str(&String::from_utf8_lossy(&data));
+
+        let Some((complete, rest)) = partial.rsplit_once('\n') else {
+            continue;
+        };
+        let lines: Vec<String> = complete.lines().map(str::to_string).collect();
+
+        partial = rest.to_string();
+
+        if state.supervisor.pid().is_some() {
+            continue;
+        }
+
+        for line in lines {
+            state.supervisor.console().push(line).await;
+        }
+    }
+}
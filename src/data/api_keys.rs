@@ -0,0 +1,420 @@
+use std::{fmt, io, path::PathBuf};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::RwLock};
+
+use super::{
+    role::Role,
+    users::{Account, Users},
+};
+use crate::utils;
+
+const PREFIX: &str = "hk_";
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read api keys file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse api keys file: {error}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Scope {
+    #[serde(rename = "admin")]
+    Admin,
+    #[serde(rename = "console:read")]
+    ConsoleRead,
+    #[serde(rename = "console:write")]
+    ConsoleWrite,
+    #[serde(rename = "mods:read")]
+    ModsRead,
+    #[serde(rename = "mods:write")]
+    ModsWrite,
+    #[serde(rename = "players:read")]
+    PlayersRead,
+    #[serde(rename = "server:config")]
+    ServerConfig,
+    #[serde(rename = "server:power")]
+    ServerPower,
+    #[serde(rename = "server:read")]
+    ServerRead,
+    #[serde(rename = "server:restart")]
+    ServerRestart,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Admin => write!(f, "admin"),
+            Scope::ConsoleRead => write!(f, "console:read"),
+            Scope::ConsoleWrite => write!(f, "console:write"),
+            Scope::ModsRead => write!(f, "mods:read"),
+            Scope::ModsWrite => write!(f, "mods:write"),
+            Scope::PlayersRead => write!(f, "players:read"),
+            Scope::ServerConfig => write!(f, "server:config"),
+            Scope::ServerPower => write!(f, "server:power"),
+            Scope::ServerRead => write!(f, "server:read"),
+            Scope::ServerRestart => write!(f, "server:restart"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub(crate) struct ApiKey {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    #[serde(skip)]
+    hash: String,
+    pub(crate) scopes: Vec<Scope>,
+    // Keys never get more than the account that created them.
+    pub(crate) role: Role,
+    pub(crate) created_by: String,
+    #[serde(with = "crate::data::date_format::user")]
+    pub(crate) created_at: DateTime<FixedOffset>,
+    #[serde(with = "crate::data::date_format::user_optional")]
+    pub(crate) expires_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredKey {
+    #[serde(flatten)]
+    key: ApiKey,
+    hash: String,
+}
+
+impl ApiKey {
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Local::now())
+    }
+
+    // The creator may have been removed or demoted since the key was made.
+    pub(crate) fn account(self, users: &Users) -> Option<Account> {
+        let creator = users.account(&self.created_by)?;
+
+        Some(Account {
+            name: format!("key:{}", self.name),
+            role: self.role.min(creator.role),
+            scopes: Some(self.scopes),
+            session: None,
+        })
+    }
+}
+
+// Keys look like `hk_<id>_<secret>`, only a hash of the secret is stored.
+pub(crate) struct ApiKeys {
+    path: PathBuf,
+    keys: RwLock<Vec<ApiKey>>,
+}
+
+impl ApiKeys {
+    pub(crate) async fn load(path: PathBuf) -> Result<ApiKeys, Error> {
+        let keys = match fs::read(&path).await {
+            Ok(data) => serde_json::from_slice::<Vec<StoredKey>>(&data)
+                .map_err(Error::Parse)?
+                .into_iter()
+                .map(|stored| ApiKey {
+                    hash: stored.hash,
+                    ..stored.key
+                })
+                .collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(Error::Read(error)),
+        };
+
+        Ok(Self {
+            path,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    pub(crate) fn is_key(token: &str) -> bool {
+        token.starts_with(PREFIX)
+    }
+
+    pub(crate) async fn list(&self) -> Vec<ApiKey> {
+        self.keys.read().await.clone()
+    }
+
+    pub(crate) async fn create(
+        &self,
+        name: String,
+        scopes: Vec<Scope>,
+        role: Role,
+        created_by: String,
+        expires_at: Option<DateTime<FixedOffset>>,
+    ) -> io::Result<(ApiKey, String)> {
+        let id = utils::generate_password(12);
+        let secret = utils::generate_password(40);
+        let key = ApiKey {
+            id: id.clone(),
+            name,
+            hash: utils::digest(&secret),
+            scopes,
+            role,
+            created_by,
+            created_at: Local::now().into(),
+            expires_at,
+        };
+        let mut keys = self.keys.write().await;
+        // Only kept once it is saved, a key reported as failed must not work.
+        let mut changed = keys.clone();
+
+        changed.push(key.clone());
+        self.save(&changed).await?;
+        *keys = changed;
+
+        Ok((key, format!("{PREFIX}{id}_{secret}")))
+    }
+
+    pub(crate) async fn revoke(&self, id: &str) -> io::Result<bool> {
+        let mut keys = self.keys.write().await;
+        let mut changed = keys.clone();
+
+        changed.retain(|key| key.id != id);
+
+        if changed.len() == keys.len() {
+            return Ok(false);
+        }
+
+        self.save(&changed).await?;
+        *keys = changed;
+
+        Ok(true)
+    }
+
+    pub(crate) async fn verify(&self, token: &str) -> Option<ApiKey> {
+        let (id, secret) = token.strip_prefix(PREFIX)?.split_once('_')?;
+        let keys = self.keys.read().await;
+        let key = keys.iter().find(|key| key.id == id)?;
+
+        if key.is_expired()
+            || !utils::constant_time_eq(utils::digest(secret).as_bytes(), key.hash.as_bytes())
+        {
+            return None;
+        }
+
+        Some(key.clone())
+    }
+
+    async fn save(&self, keys: &[ApiKey]) -> io::Result<()> {
+        let stored: Vec<StoredKey> = keys
+            .iter()
+            .map(|key| StoredKey {
+                key: key.clone(),
+                hash: key.hash.clone(),
+            })
+            .collect();
+        let data = serde_json::to_vec_pretty(&stored).expect("failed to serialize api keys");
+
+        utils::write_atomic(&self.path, data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::data::users::User;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("habwa-keys-{}-{name}.json", std::process::id()))
+    }
+
+    async fn keys(name: &str) -> ApiKeys {
+        let path = path(name);
+        let _ = fs::remove_file(&path).await;
+
+        ApiKeys::load(path).await.expect("no keys file")
+    }
+
+    async fn create(keys: &ApiKeys, expires_at: Option<DateTime<FixedOffset>>) -> (ApiKey, String) {
+        keys.create(
+            "deploy".to_string(),
+            vec![Scope::ServerRead],
+            Role::Operator,
+            "bob".to_string(),
+            expires_at,
+        )
+        .await
+        .expect("save keys")
+    }
+
+    #[tokio::test]
+    async fn created_keys_verify_and_persist() {
+        let keys = keys("persist").await;
+        let (key, token) = create(&keys, None).await;
+
+        assert!(ApiKeys::is_key(&token));
+        assert_eq!(keys.verify(&token).await.expect("valid key").id, key.id);
+
+        let loaded = ApiKeys::load(path("persist")).await.expect("keys file");
+
+        assert_eq!(loaded.verify(&token).await.expect("valid key").id, key.id);
+        assert_eq!(loaded.list().await.len(), 1);
+
+        fs::remove_file(path("persist"))
+            .await
+            .expect("remove keys file");
+    }
+
+    #[tokio::test]
+    async fn wrong_secrets_are_rejected() {
+        let keys = keys("secret").await;
+        let (key, token) = create(&keys, None).await;
+        let (prefix, secret) = token.rsplit_once('_').unwrap();
+        let mut wrong = secret.to_string();
+
+        wrong.replace_range(..1, if secret.starts_with('a') { "b" } else { "a" });
+
+        assert!(keys.verify(&format!("{prefix}_{wrong}")).await.is_none());
+        assert!(keys.verify(&format!("{prefix}_")).await.is_none());
+        assert!(keys
+            .verify(&format!("{PREFIX}unknown_{secret}"))
+            .await
+            .is_none());
+        assert!(keys.verify(&format!("{PREFIX}{}", key.id)).await.is_none());
+        assert!(keys.verify(secret).await.is_none());
+
+        fs::remove_file(path("secret"))
+            .await
+            .expect("remove keys file");
+    }
+
+    #[tokio::test]
+    async fn expired_keys_are_rejected() {
+        let keys = keys("expiry").await;
+        let now: DateTime<FixedOffset> = Local::now().into();
+        let (_, expired) = create(&keys, Some(now - Duration::seconds(1))).await;
+        let (_, valid) = create(&keys, Some(now + Duration::hours(1))).await;
+
+        assert!(keys.verify(&expired).await.is_none());
+        assert!(keys.verify(&valid).await.is_some());
+
+        fs::remove_file(path("expiry"))
+            .await
+            .expect("remove keys file");
+    }
+
+    #[tokio::test]
+    async fn revoked_keys_are_rejected() {
+        let keys = keys("revoke").await;
+        let (key, token) = create(&keys, None).await;
+        let (_, other) = create(&keys, None).await;
+
+        assert!(keys.revoke(&key.id).await.expect("save keys"));
+        assert!(!keys.revoke(&key.id).await.expect("nothing to save"));
+        assert!(keys.verify(&token).await.is_none());
+        assert!(keys.verify(&other).await.is_some());
+
+        let loaded = ApiKeys::load(path("revoke")).await.expect("keys file");
+
+        assert!(loaded.verify(&token).await.is_none());
+
+        fs::remove_file(path("revoke"))
+            .await
+            .expect("remove keys file");
+    }
+
+    #[tokio::test]
+    async fn failed_saves_change_nothing() {
+        let keys = ApiKeys::load(PathBuf::from("/nonexistent/api_keys.json"))
+            .await
+            .expect("no keys file");
+
+        assert!(keys
+            .create(
+                "deploy".to_string(),
+                vec![],
+                Role::Viewer,
+                "bob".to_string(),
+                None
+            )
+            .await
+            .is_err());
+        assert!(keys.list().await.is_empty());
+
+        let keys = self::keys("unsaved").await;
+        let (key, token) = create(&keys, None).await;
+
+        // A folder in its place makes the rename fail.
+        fs::remove_file(path("unsaved"))
+            .await
+            .expect("remove keys file");
+        fs::create_dir(path("unsaved"))
+            .await
+            .expect("create folder");
+
+        assert!(keys.revoke(&key.id).await.is_err());
+        assert!(keys.verify(&token).await.is_some());
+
+        fs::remove_dir(path("unsaved"))
+            .await
+            .expect("remove folder");
+    }
+
+    #[tokio::test]
+    async fn bad_files_are_errors() {
+        assert!(matches!(
+            ApiKeys::load(std::env::temp_dir()).await,
+            Err(Error::Read(_))
+        ));
+
+        fs::write(path("corrupt"), "[{")
+            .await
+            .expect("write keys file");
+
+        assert!(matches!(
+            ApiKeys::load(path("corrupt")).await,
+            Err(Error::Parse(_))
+        ));
+
+        fs::remove_file(path("corrupt"))
+            .await
+            .expect("remove keys file");
+    }
+
+    #[tokio::test]
+    async fn keys_are_capped_by_their_creator() {
+        let keys = keys("creator").await;
+        let (key, _) = create(&keys, None).await;
+        let user = |role| User {
+            password: String::new(),
+            role,
+        };
+
+        let admin = Users::new(HashMap::from([("bob".to_string(), user(Role::Admin))]));
+        let account = key.clone().account(&admin).expect("creator exists");
+
+        assert_eq!(account.name, "key:deploy");
+        assert_eq!(account.role, Role::Operator);
+        assert_eq!(account.scopes, Some(vec![Scope::ServerRead]));
+
+        let demoted = Users::new(HashMap::from([("bob".to_string(), user(Role::Viewer))]));
+
+        assert_eq!(
+            key.clone().account(&demoted).expect("creator exists").role,
+            Role::Viewer
+        );
+
+        let removed = Users::new(HashMap::from([("alice".to_string(), user(Role::Admin))]));
+
+        assert!(key.account(&removed).is_none());
+
+        fs::remove_file(path("creator"))
+            .await
+            .expect("remove keys file");
+    }
+}
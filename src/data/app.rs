@@ -1,49 +1,139 @@
-use std::path::PathBuf;
+use std::{io, path::PathBuf};
 
-use rcon_client::RCONClient;
-use tokio::sync::Mutex;
+use tokio::{
+    fs,
+    sync::{broadcast, Mutex, RwLock},
+};
 
-use crate::loaders::forge::Mod;
+use crate::{config::Cors, loaders::forge::Mod, rcon, supervisor::Supervisor, watcher::Change};
 
-use super::server;
+use super::{
+    api_keys::ApiKeys,
+    audit::AuditLog,
+    commands::CommandPolicy,
+    ip_filter::IpFilter,
+    role::Role,
+    server,
+    session::Sessions,
+    throttle::Throttle,
+    totp::Totp,
+    users::{Account, Users},
+};
 
 pub(crate) struct State {
-    pub(crate) rcon: Mutex<Option<RCONClient>>,
-    pub(crate) properties: server::Properties,
+    pub(crate) rcon: rcon::Client,
+    pub(crate) properties: RwLock<server::Properties>,
+    // Held by everything that rewrites server.properties.
+    pub(crate) properties_file: Mutex<()>,
     pub(crate) path: PathBuf,
-    pub(crate) access_token: Option<String>,
-    pub(crate) encryption_key: Option<String>,
+    pub(crate) users: Users,
     pub(crate) mods: Mutex<Vec<Mod>>,
+    pub(crate) user_cache: RwLock<Vec<server::CachedUser>>,
+    // Files reloaded because they changed on disk.
+    pub(crate) changes: broadcast::Sender<Change>,
+    pub(crate) supervisor: Supervisor,
+    pub(crate) commands: CommandPolicy,
+    pub(crate) sessions: Sessions,
+    pub(crate) throttle: Throttle,
+    pub(crate) api_keys: ApiKeys,
+    pub(crate) audit: AuditLog,
+    pub(crate) totp: Totp,
+    pub(crate) cors: std::sync::RwLock<Cors>,
+    pub(crate) ip_filter: std::sync::RwLock<IpFilter>,
+    // Set by `--insecure-no-auth`, the only way to run without accounts.
+    pub(crate) insecure: bool,
 }
 
 impl State {
-    pub(crate) fn new<T: Into<String>>(
-        rcon: Option<RCONClient>,
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        rcon: rcon::Client,
         path: PathBuf,
         properties: server::Properties,
-        token: Option<T>,
-        key: Option<T>,
+        users: Users,
         mods: Vec<Mod>,
+        user_cache: Vec<server::CachedUser>,
+        supervisor: Supervisor,
+        commands: CommandPolicy,
+        sessions: Sessions,
+        throttle: Throttle,
+        api_keys: ApiKeys,
+        audit: AuditLog,
+        totp: Totp,
+        cors: Cors,
+        ip_filter: IpFilter,
+        insecure: bool,
     ) -> State {
-        let mut access_token = None;
+        Self {
+            rcon,
+            properties: RwLock::new(properties),
+            properties_file: Mutex::new(()),
+            path,
+            users,
+            mods: Mutex::new(mods),
+            user_cache: RwLock::new(user_cache),
+            changes: broadcast::channel(16).0,
+            supervisor,
+            commands,
+            sessions,
+            throttle,
+            api_keys,
+            audit,
+            totp,
+            cors: std::sync::RwLock::new(cors),
+            ip_filter: std::sync::RwLock::new(ip_filter),
+            insecure,
+        }
+    }
 
-        if let Some(token) = token {
-            access_token = Some(token.into());
+    // Without any accounts the panel is only open when it was started with
+    // `--insecure-no-auth`, otherwise nobody gets in.
+    pub(crate) async fn authenticate(&self, token: Option<&str>) -> Option<Account> {
+        if self.insecure && self.users.is_empty() {
+            return Some(Account {
+                name: "anonymous".to_string(),
+                role: Role::Admin,
+                scopes: None,
+                session: None,
+            });
         }
 
-        let mut encryption_key = None;
+        let token = token?;
 
-        if let Some(key) = key {
-            encryption_key = Some(key.into());
+        if ApiKeys::is_key(token) {
+            return self.api_keys.verify(token).await?.account(&self.users);
         }
 
-        Self {
-            rcon: Mutex::new(rcon),
-            properties,
-            path,
-            access_token,
-            encryption_key,
-            mods: Mutex::new(mods),
+        let claims = self.sessions.verify(token).await?;
+        let mut account = self.users.account(&claims.user)?;
+
+        account.session = Some(claims);
+
+        Some(account)
+    }
+
+    pub(crate) async fn reload_properties(&self) -> io::Result<()> {
+        let file = fs::read_to_string(self.path.join("server.properties")).await?;
+
+        self.set_properties(server::Properties::new(file)).await;
+
+        Ok(())
+    }
+
+    pub(crate) async fn set_properties(&self, properties: server::Properties) {
+        let current = self.properties.read().await;
+        // Reconfiguring drops the connection, which most changes do not need.
+        let moved = current.rcon_address() != properties.rcon_address()
+            || current.rcon.password != properties.rcon.password;
+
+        drop(current);
+
+        if moved {
+            self.rcon
+                .configure(properties.rcon_address(), properties.rcon.password.clone())
+                .await;
         }
+
+        *self.properties.write().await = properties;
     }
 }
@@ -0,0 +1,109 @@
+use std::{io, net::IpAddr, path::PathBuf};
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Outcome {
+    Success,
+    Failure,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub(crate) struct Entry {
+    #[serde(with = "crate::data::date_format::user")]
+    pub(crate) time: DateTime<FixedOffset>,
+    pub(crate) actor: String,
+    pub(crate) ip: Option<IpAddr>,
+    pub(crate) action: String,
+    pub(crate) target: Option<String>,
+    pub(crate) result: Outcome,
+    pub(crate) status: u16,
+}
+
+// Handlers put this in their response extensions to name what they acted on.
+#[derive(Clone)]
+pub(crate) struct Target(pub(crate) String);
+
+pub(crate) struct Filter {
+    pub(crate) actor: Option<String>,
+    pub(crate) action: Option<String>,
+    pub(crate) since: Option<DateTime<FixedOffset>>,
+    pub(crate) until: Option<DateTime<FixedOffset>>,
+    pub(crate) limit: usize,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| &entry.actor == actor)
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|action| &entry.action == action)
+            && self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time <= until)
+    }
+}
+
+// Entries are only ever appended, one JSON object per line.
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(path: PathBuf) -> AuditLog {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub(crate) async fn record(&self, entry: Entry) {
+        let mut line = serde_json::to_string(&entry).expect("failed to serialize audit entry");
+
+        line.push('\n');
+
+        let _lock = self.lock.lock().await;
+        let result = async {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await
+        }
+        .await;
+
+        if let Err(error) = result {
+            println!("Failed to write audit entry: {error}");
+        }
+    }
+
+    // Newest entries first.
+    pub(crate) async fn query(&self, filter: &Filter) -> io::Result<Vec<Entry>> {
+        let data = match fs::read_to_string(&self.path).await {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+
+        Ok(data
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+            .filter(|entry| filter.matches(entry))
+            .take(filter.limit)
+            .collect())
+    }
+}
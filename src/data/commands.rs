@@ -0,0 +1,353 @@
+use std::{collections::HashMap, fmt, io, path::Path};
+
+use lazy_regex::{lazy_regex, Lazy};
+use regex::Regex;
+use serde::Deserialize;
+use tokio::fs;
+
+use super::role::Role;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(toml::de::Error),
+    Pattern(regex::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read command policy file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse command policy file: {error}"),
+            Error::Pattern(error) => write!(f, "invalid pattern in command policy file: {error}"),
+        }
+    }
+}
+
+static NAMESPACE_REX: Lazy<Regex> = lazy_regex!(r"^[a-z0-9_.-]+:");
+static EXECUTE_RUN_REX: Lazy<Regex> = lazy_regex!(r"(?is)^execute\s(?:.*?\s)?run\s+(.*)$");
+
+// The default allow lists. Operators get the moderator commands and the
+// gameplay ones, but nothing that stops the server or hands out op.
+const MODERATOR_COMMANDS: &[&str] = &[
+    "list",
+    "kick",
+    "ban",
+    "ban-ip",
+    "banlist",
+    "pardon",
+    "pardon-ip",
+    "whitelist",
+    "say",
+    "tell",
+    "msg",
+];
+const OPERATOR_COMMANDS: &[&str] = &[
+    "advancement",
+    "attribute",
+    "bossbar",
+    "clear",
+    "clone",
+    "data",
+    "defaultgamemode",
+    "difficulty",
+    "effect",
+    "enchant",
+    "execute",
+    "experience",
+    "fill",
+    "forceload",
+    "function",
+    "gamemode",
+    "gamerule",
+    "give",
+    "item",
+    "kill",
+    "locate",
+    "loot",
+    "me",
+    "particle",
+    "playsound",
+    "recipe",
+    "save-all",
+    "schedule",
+    "scoreboard",
+    "seed",
+    "setblock",
+    "setworldspawn",
+    "spawnpoint",
+    "spreadplayers",
+    "summon",
+    "tag",
+    "team",
+    "teleport",
+    "tellraw",
+    "time",
+    "title",
+    "tp",
+    "trigger",
+    "weather",
+    "worldborder",
+    "xp",
+];
+
+#[derive(Deserialize, Clone, Default)]
+pub(crate) struct Rules {
+    #[serde(default)]
+    pub(crate) allow: Vec<String>,
+    #[serde(default)]
+    pub(crate) deny: Vec<String>,
+}
+
+struct CompiledRules {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+pub(crate) struct CommandPolicy {
+    rules: HashMap<Role, CompiledRules>,
+}
+
+impl CommandPolicy {
+    pub(crate) fn new(rules: HashMap<Role, Rules>) -> Result<CommandPolicy, regex::Error> {
+        let mut compiled = HashMap::new();
+
+        for (role, rules) in rules {
+            compiled.insert(
+                role,
+                CompiledRules {
+                    allow: compile(&rules.allow)?,
+                    deny: compile(&rules.deny)?,
+                },
+            );
+        }
+
+        Ok(Self { rules: compiled })
+    }
+
+    pub(crate) async fn load(path: &Path) -> Result<CommandPolicy, Error> {
+        let data = fs::read_to_string(path).await.map_err(Error::Read)?;
+
+        CommandPolicy::parse(&data)
+    }
+
+    fn parse(data: &str) -> Result<CommandPolicy, Error> {
+        let rules: HashMap<Role, Rules> = toml::from_str(data).map_err(Error::Parse)?;
+
+        CommandPolicy::new(rules).map_err(Error::Pattern)
+    }
+
+    // The command and everything it runs through `execute … run` all have to be
+    // allowed.
+    pub(crate) fn is_allowed(&self, role: Role, command: &str) -> bool {
+        let Some(rules) = self.rules.get(&role) else {
+            return false;
+        };
+
+        layers(command).iter().all(|command| {
+            rules.allow.iter().any(|pattern| pattern.is_match(command))
+                && !rules.deny.iter().any(|pattern| pattern.is_match(command))
+        })
+    }
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        let rules = |allow: &[&str], deny: &[&str]| Rules {
+            allow: allow.iter().map(|pattern| pattern.to_string()).collect(),
+            deny: deny.iter().map(|pattern| pattern.to_string()).collect(),
+        };
+
+        CommandPolicy::new(HashMap::from([
+            (Role::Admin, rules(&["*"], &[])),
+            (
+                Role::Operator,
+                rules(
+                    &[MODERATOR_COMMANDS, OPERATOR_COMMANDS].concat(),
+                    &["stop", "op", "deop"],
+                ),
+            ),
+            (Role::Moderator, rules(MODERATOR_COMMANDS, &[])),
+            (Role::Viewer, rules(&["list"], &[])),
+        ]))
+        .expect("invalid default command policy")
+    }
+}
+
+// `kick` matches the kick command with any arguments, patterns containing `*`
+// or spaces are matched against the whole command line instead.
+fn compile(patterns: &[String]) -> Result<Vec<Regex>, regex::Error> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern.trim().trim_start_matches('/');
+            let escaped = regex::escape(pattern).replace(r"\*", ".*");
+
+            if pattern.contains('*') || pattern.contains(' ') {
+                Regex::new(&format!("(?is)^{escaped}$"))
+            } else {
+                Regex::new(&format!(r"(?is)^{escaped}(\s.*)?$"))
+            }
+        })
+        .collect()
+}
+
+// The command followed by every command it runs through `execute … run`, with
+// namespaces like `minecraft:` taken off so they cannot hide a command.
+fn layers(command: &str) -> Vec<String> {
+    let mut layers = vec![];
+    let mut rest = command.to_string();
+
+    loop {
+        let command = rest.trim().trim_start_matches('/');
+        let command = NAMESPACE_REX.replace(command, "").to_string();
+        let next = EXECUTE_RUN_REX
+            .captures(&command)
+            .map(|captures| captures[1].to_string());
+
+        layers.push(command);
+
+        match next {
+            Some(next) => rest = next,
+            None => return layers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<Regex> {
+        compile(
+            &patterns
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn compile_matches_a_command_with_any_arguments() {
+        let kick = &patterns(&["kick"])[0];
+
+        assert!(kick.is_match("kick"));
+        assert!(kick.is_match("kick Steve griefing"));
+        assert!(kick.is_match("KICK Steve"));
+        assert!(!kick.is_match("kickall"));
+        assert!(!kick.is_match("say kick"));
+    }
+
+    #[test]
+    fn compile_matches_wildcards_and_spaces_against_the_whole_line() {
+        let patterns = patterns(&["/gamerule *", "time set day", "*"]);
+
+        assert!(patterns[0].is_match("gamerule keepInventory true"));
+        assert!(!patterns[0].is_match("gamerule"));
+        assert!(patterns[1].is_match("time set day"));
+        assert!(!patterns[1].is_match("time set day extra"));
+        assert!(patterns[2].is_match("anything at all"));
+    }
+
+    #[test]
+    fn compile_escapes_regex_syntax() {
+        let patterns = patterns(&["say (hi)", "tp.*"]);
+
+        assert!(patterns[0].is_match("say (hi)"));
+        assert!(!patterns[0].is_match("say hi"));
+        assert!(!patterns[1].is_match("tpx"));
+    }
+
+    #[test]
+    fn default_policy_follows_roles() {
+        let policy = CommandPolicy::default();
+
+        assert!(policy.is_allowed(Role::Admin, "stop"));
+        assert!(policy.is_allowed(Role::Operator, "/give Steve diamond 64"));
+        assert!(!policy.is_allowed(Role::Operator, "stop"));
+        assert!(policy.is_allowed(Role::Moderator, "kick Steve"));
+        assert!(!policy.is_allowed(Role::Moderator, "give Steve diamond"));
+        assert!(policy.is_allowed(Role::Viewer, "list"));
+        assert!(!policy.is_allowed(Role::Viewer, "say hi"));
+    }
+
+    #[test]
+    fn operators_cannot_get_around_the_policy() {
+        let policy = CommandPolicy::default();
+
+        for command in [
+            "stop",
+            "/stop",
+            "minecraft:stop",
+            "/minecraft:op Foo",
+            "deop Foo",
+            "execute run op Foo",
+            "execute as @a at @s run minecraft:op Foo",
+            "execute run execute run stop",
+            "  execute   run   deop Foo",
+            "reload",
+            "somemod:unknown",
+        ] {
+            assert!(
+                !policy.is_allowed(Role::Operator, command),
+                "{command} should be denied"
+            );
+        }
+
+        assert!(policy.is_allowed(Role::Operator, "execute as @a run say hi"));
+        assert!(policy.is_allowed(Role::Operator, "minecraft:give Steve diamond"));
+    }
+
+    #[test]
+    fn execute_needs_to_be_allowed_itself() {
+        let policy = CommandPolicy::default();
+
+        assert!(!policy.is_allowed(Role::Moderator, "execute run kick Steve"));
+        assert!(policy.is_allowed(Role::Moderator, "minecraft:kick Steve"));
+    }
+
+    #[test]
+    fn layers_unwrap_execute_and_namespaces() {
+        assert_eq!(layers("/say hi"), vec!["say hi"]);
+        assert_eq!(
+            layers("execute as @a run minecraft:op Foo"),
+            vec!["execute as @a run minecraft:op Foo", "op Foo"]
+        );
+        assert_eq!(layers("execute if entity @a"), vec!["execute if entity @a"]);
+    }
+
+    #[test]
+    fn parses_policy_files() {
+        let policy = CommandPolicy::parse(
+            "[moderator]\nallow = [\"say\", \"list\"]\n\n[admin]\nallow = [\"*\"]\ndeny = [\"stop\"]\n",
+        )
+        .expect("valid policy");
+
+        assert!(policy.is_allowed(Role::Moderator, "say hi"));
+        assert!(!policy.is_allowed(Role::Moderator, "kick bob"));
+        assert!(!policy.is_allowed(Role::Admin, "stop"));
+        assert!(!policy.is_allowed(Role::Viewer, "list"));
+    }
+
+    #[tokio::test]
+    async fn bad_policy_files_are_errors() {
+        assert!(matches!(
+            CommandPolicy::load(Path::new("/nonexistent/commands.toml")).await,
+            Err(Error::Read(_))
+        ));
+        assert!(matches!(
+            CommandPolicy::parse("[moderator\nallow = []"),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            CommandPolicy::parse("[owner]\nallow = []"),
+            Err(Error::Parse(_))
+        ));
+        // Patterns are escaped, only one too big to compile can fail.
+        assert!(matches!(
+            CommandPolicy::parse(&format!("[admin]\nallow = [\"{}\"]", "a*".repeat(20_000))),
+            Err(Error::Pattern(_))
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+// A network in CIDR notation, or a single address.
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub(crate) struct Network(IpNet);
+
+impl TryFrom<String> for Network {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse::<IpNet>()
+            .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+            .map(Network)
+            .map_err(|_| format!("invalid network `{value}`"))
+    }
+}
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Rules {
+    // Empty allows everyone who is not denied.
+    pub(crate) allow: Vec<Network>,
+    pub(crate) deny: Vec<Network>,
+}
+
+// The top level rules apply to the whole api, the ones under `routes` only to
+// paths under their prefix, where the longest matching prefix wins.
+#[derive(Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct IpFilter {
+    pub(crate) allow: Vec<Network>,
+    pub(crate) deny: Vec<Network>,
+    pub(crate) routes: HashMap<String, Rules>,
+}
+
+impl IpFilter {
+    pub(crate) fn allows(&self, ip: IpAddr, path: &str) -> bool {
+        // IPv4 clients show up as mapped addresses on a dual stack socket.
+        let ip = ip.to_canonical();
+
+        if !allows(&self.allow, &self.deny, ip) {
+            return false;
+        }
+
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| is_under(path, prefix))
+            .max_by_key(|(prefix, _)| prefix.trim_end_matches('/').len())
+            .is_none_or(|(_, rules)| allows(&rules.allow, &rules.deny, ip))
+    }
+}
+
+fn allows(allow: &[Network], deny: &[Network], ip: IpAddr) -> bool {
+    (allow.is_empty() || allow.iter().any(|network| network.0.contains(&ip)))
+        && !deny.iter().any(|network| network.0.contains(&ip))
+}
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(data: &str) -> IpFilter {
+        toml::from_str(data).expect("valid filter")
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().expect("valid address")
+    }
+
+    #[test]
+    fn empty_allows_everyone() {
+        let filter = IpFilter::default();
+
+        assert!(filter.allows(ip("203.0.113.9"), "/server"));
+        assert!(filter.allows(ip("2001:db8::1"), "/"));
+    }
+
+    #[test]
+    fn mapped_ipv4_addresses_match_ipv4_networks() {
+        let filter = filter(
+            r#"
+            allow = ["10.0.0.0/8"]
+            deny = ["10.0.0.5"]
+            "#,
+        );
+
+        assert!(filter.allows(ip("::ffff:10.1.2.3"), "/"));
+        assert!(!filter.allows(ip("::ffff:192.168.0.1"), "/"));
+        assert!(!filter.allows(ip("::ffff:10.0.0.5"), "/"));
+    }
+
+    #[test]
+    fn deny_overrides_allow() {
+        let filter = filter(
+            r#"
+            allow = ["10.0.0.0/8", "2001:db8::/32"]
+            deny = ["10.0.0.5", "10.1.0.0/16", "2001:db8:bad::/48"]
+            "#,
+        );
+
+        assert!(filter.allows(ip("10.0.0.6"), "/"));
+        assert!(!filter.allows(ip("10.0.0.5"), "/"));
+        assert!(!filter.allows(ip("10.1.200.3"), "/"));
+        assert!(filter.allows(ip("2001:db8::1"), "/"));
+        assert!(!filter.allows(ip("2001:db8:bad::1"), "/"));
+        assert!(!filter.allows(ip("192.168.0.1"), "/"));
+    }
+
+    #[test]
+    fn routes_only_cover_whole_path_segments() {
+        for prefix in ["/server", "/server/"] {
+            let filter = filter(&format!(
+                r#"
+                [routes."{prefix}"]
+                deny = ["0.0.0.0/0"]
+                "#
+            ));
+            let client = ip("203.0.113.9");
+
+            assert!(!filter.allows(client, "/server"), "{prefix}");
+            assert!(!filter.allows(client, "/server/"), "{prefix}");
+            assert!(!filter.allows(client, "/server/config"), "{prefix}");
+            assert!(filter.allows(client, "/serverx"), "{prefix}");
+            assert!(filter.allows(client, "/servers/1"), "{prefix}");
+            assert!(filter.allows(client, "/"), "{prefix}");
+        }
+    }
+
+    #[test]
+    fn the_longest_prefix_wins() {
+        let filter = filter(
+            r#"
+            [routes."/server"]
+            allow = ["10.0.0.0/8"]
+
+            [routes."/server/console/"]
+            allow = ["192.168.0.0/16"]
+            "#,
+        );
+        let lan = ip("192.168.1.20");
+        let vpn = ip("10.0.0.7");
+
+        assert!(filter.allows(lan, "/server/console"));
+        assert!(filter.allows(lan, "/server/console/stream"));
+        assert!(!filter.allows(vpn, "/server/console"));
+        assert!(!filter.allows(lan, "/server/config"));
+        assert!(filter.allows(vpn, "/server/config"));
+        // Only `/server` covers this one.
+        assert!(!filter.allows(lan, "/server/consoles"));
+        assert!(filter.allows(vpn, "/server/consoles"));
+    }
+
+    #[test]
+    fn top_level_rules_apply_to_every_route() {
+        let filter = filter(
+            r#"
+            deny = ["192.168.1.66"]
+
+            [routes."/server/console"]
+            allow = ["192.168.0.0/16"]
+            "#,
+        );
+
+        assert!(filter.allows(ip("192.168.1.20"), "/server/console"));
+        assert!(!filter.allows(ip("192.168.1.66"), "/server/console"));
+        assert!(!filter.allows(ip("192.168.1.66"), "/auth"));
+    }
+
+    #[test]
+    fn networks_are_checked_when_loaded() {
+        assert!(toml::from_str::<IpFilter>(r#"allow = ["10.0.0.0/33"]"#).is_err());
+        assert!(toml::from_str::<IpFilter>(r#"allow = ["localhost"]"#).is_err());
+        assert!(toml::from_str::<IpFilter>(r#"alow = ["10.0.0.1"]"#).is_err());
+    }
+}
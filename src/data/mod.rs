@@ -1,8 +1,17 @@
 use lazy_regex::{Lazy, lazy_regex};
 use regex::Regex;
 
+pub(crate) mod api_keys;
 pub(crate) mod app;
+pub(crate) mod audit;
+pub(crate) mod commands;
 pub(crate) mod server;
 pub(crate) mod date_format;
+pub(crate) mod ip_filter;
+pub(crate) mod role;
+pub(crate) mod session;
+pub(crate) mod throttle;
+pub(crate) mod totp;
+pub(crate) mod users;
 
 pub(crate) static UUID_REX: Lazy<Regex> = lazy_regex!("([A-f0-9]{8}-[A-f0-9]{4}-[A-f0-9]{4}-[A-f0-9]{4}-[A-f0-9]{12})");
\ No newline at end of file
@@ -0,0 +1,23 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Role {
+    Viewer,
+    Moderator,
+    Operator,
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Moderator => write!(f, "moderator"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
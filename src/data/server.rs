@@ -1,26 +1,111 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
 use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 
-use crate::parsers;
+use crate::parsers::properties::Document;
 
-#[derive(Serialize, Clone)]
+// Every key of a vanilla server.properties, named after the key with `-` and
+// `.` turned into `_`. Missing or unreadable keys keep the vanilla default.
+// The address, whitelist and rcon keys keep the names they had before the
+// rest were added, which is what clients already read.
+#[derive(Deserialize, Serialize, Clone)]
 pub(crate) struct Properties {
-    pub(crate) ip: Option<String>,
-    pub(crate) port: u32,
+    pub(crate) accepts_transfers: bool,
+    pub(crate) allow_flight: bool,
+    pub(crate) allow_nether: bool,
+    pub(crate) broadcast_console_to_ops: bool,
+    pub(crate) broadcast_rcon_to_ops: bool,
+    pub(crate) bug_report_link: Option<String>,
+    pub(crate) difficulty: Difficulty,
+    pub(crate) enable_command_block: bool,
+    pub(crate) enable_jmx_monitoring: bool,
+    pub(crate) enable_query: bool,
+    pub(crate) enable_status: bool,
+    pub(crate) enforce_secure_profile: bool,
+    pub(crate) enforce_whitelist: bool,
+    pub(crate) entity_broadcast_range_percentage: u32,
+    pub(crate) force_gamemode: bool,
+    pub(crate) function_permission_level: u8,
+    pub(crate) gamemode: Gamemode,
+    pub(crate) generate_structures: bool,
+    pub(crate) generator_settings: String,
+    pub(crate) hardcore: bool,
+    pub(crate) hide_online_players: bool,
+    pub(crate) initial_disabled_packs: Option<String>,
+    pub(crate) initial_enabled_packs: Option<String>,
+    pub(crate) level_name: String,
+    pub(crate) level_seed: Option<String>,
+    pub(crate) level_type: String,
+    pub(crate) log_ips: bool,
+    pub(crate) max_chained_neighbor_updates: i32,
     pub(crate) max_players: u32,
-    pub(crate) motd: Option<String>,
+    pub(crate) max_tick_time: i64,
+    pub(crate) max_world_size: u32,
+    pub(crate) motd: String,
+    pub(crate) network_compression_threshold: i32,
     pub(crate) online_mode: bool,
+    pub(crate) op_permission_level: u8,
+    pub(crate) pause_when_empty_seconds: i32,
+    pub(crate) player_idle_timeout: u32,
+    pub(crate) prevent_proxy_connections: bool,
     pub(crate) pvp: bool,
-    pub(crate) hardcore: bool,
-    pub(crate) rcon: RCONProperty,
-    pub(crate) whitelist: bool,
+    pub(crate) query_port: u16,
+    pub(crate) rate_limit: u32,
+    pub(crate) rcon: Rcon,
+    pub(crate) region_file_compression: String,
+    pub(crate) require_resource_pack: bool,
+    pub(crate) resource_pack: Option<String>,
+    pub(crate) resource_pack_id: Option<String>,
+    pub(crate) resource_pack_prompt: Option<String>,
+    pub(crate) resource_pack_sha1: Option<String>,
+    #[serde(rename = "ip")]
+    pub(crate) server_ip: Option<String>,
+    #[serde(rename = "port")]
+    pub(crate) server_port: u16,
+    pub(crate) simulation_distance: u32,
+    pub(crate) spawn_animals: bool,
+    pub(crate) spawn_monsters: bool,
+    pub(crate) spawn_npcs: bool,
+    pub(crate) spawn_protection: u32,
+    pub(crate) sync_chunk_writes: bool,
+    pub(crate) text_filtering_config: Option<String>,
+    pub(crate) text_filtering_version: u8,
+    pub(crate) use_native_transport: bool,
+    pub(crate) view_distance: u32,
+    #[serde(rename = "whitelist")]
+    pub(crate) white_list: bool,
+    // Keys vanilla does not know about, mostly added by mods.
+    pub(crate) extra: BTreeMap<String, String>,
 }
 
-#[derive(Serialize, Clone)]
-pub(crate) struct RCONProperty {
+// `enable-rcon`, `rcon.password` and `rcon.port`.
+#[derive(Deserialize, Serialize, Clone)]
+pub(crate) struct Rcon {
     pub(crate) enabled: bool,
     pub(crate) password: Option<String>,
-    pub(crate) port: u32,
+    pub(crate) port: u16,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Difficulty {
+    Peaceful,
+    Easy,
+    Normal,
+    Hard,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Gamemode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -41,43 +126,704 @@ pub(crate) struct Player {
     pub(crate) last_join_time: Option<DateTime<FixedOffset>>,
 }
 
+impl Default for Properties {
+    fn default() -> Self {
+        Self {
+            accepts_transfers: false,
+            allow_flight: false,
+            allow_nether: true,
+            broadcast_console_to_ops: true,
+            broadcast_rcon_to_ops: true,
+            bug_report_link: None,
+            difficulty: Difficulty::Easy,
+            enable_command_block: false,
+            enable_jmx_monitoring: false,
+            enable_query: false,
+            enable_status: true,
+            enforce_secure_profile: true,
+            enforce_whitelist: false,
+            entity_broadcast_range_percentage: 100,
+            force_gamemode: false,
+            function_permission_level: 2,
+            gamemode: Gamemode::Survival,
+            generate_structures: true,
+            generator_settings: "{}".to_string(),
+            hardcore: false,
+            hide_online_players: false,
+            initial_disabled_packs: None,
+            initial_enabled_packs: Some("vanilla".to_string()),
+            level_name: "world".to_string(),
+            level_seed: None,
+            level_type: "minecraft:normal".to_string(),
+            log_ips: true,
+            max_chained_neighbor_updates: 1_000_000,
+            max_players: 20,
+            max_tick_time: 60_000,
+            max_world_size: 29_999_984,
+            motd: "A Minecraft Server".to_string(),
+            network_compression_threshold: 256,
+            online_mode: true,
+            op_permission_level: 4,
+            pause_when_empty_seconds: 60,
+            player_idle_timeout: 0,
+            prevent_proxy_connections: false,
+            pvp: true,
+            query_port: 25565,
+            rate_limit: 0,
+            rcon: Rcon {
+                enabled: false,
+                password: None,
+                port: 25575,
+            },
+            region_file_compression: "deflate".to_string(),
+            require_resource_pack: false,
+            resource_pack: None,
+            resource_pack_id: None,
+            resource_pack_prompt: None,
+            resource_pack_sha1: None,
+            server_ip: None,
+            server_port: 25565,
+            simulation_distance: 10,
+            spawn_animals: true,
+            spawn_monsters: true,
+            spawn_npcs: true,
+            spawn_protection: 16,
+            sync_chunk_writes: true,
+            text_filtering_config: None,
+            text_filtering_version: 0,
+            use_native_transport: true,
+            view_distance: 10,
+            white_list: false,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
 impl Properties {
     pub(crate) fn new<T: Into<String>>(data: T) -> Properties {
-        let properties = parsers::properties::parse(data);
-
-        let ip = properties.get("server-ip").unwrap().as_string();
-        let port = properties.get("server-port").unwrap().as_u32().unwrap();
-        let max_players = properties.get("max-players").unwrap().as_u32().unwrap();
-        let motd = properties.get("motd").unwrap().as_string();
-        let online_mode = properties.get("online-mode").unwrap().as_bool().unwrap();
-        let pvp = properties.get("pvp").unwrap().as_bool().unwrap();
-        let hardcore = properties.get("hardcore").unwrap().as_bool().unwrap();
-        let whitelist = properties.get("white-list").unwrap().as_bool().unwrap();
-
-        let rcon_enabled = properties.get("enable-rcon").unwrap().as_bool().unwrap();
-        let rcon_password = properties.get("rcon.password").unwrap().as_string();
-        let rcon_port = properties.get("rcon.port").unwrap().as_u32().unwrap();
-
-        Properties {
-            ip,
-            port,
-            max_players,
-            motd,
-            online_mode,
-            pvp,
-            hardcore,
-            rcon: RCONProperty::new(rcon_enabled, rcon_password, rcon_port),
-            whitelist,
+        Properties::from_document(&Document::parse(data))
+    }
+
+    pub(crate) fn from_document(document: &Document) -> Properties {
+        let mut values: HashMap<String, String> = document
+            .entries()
+            .filter(|(key, _)| !key.is_empty())
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut properties = Properties::default();
+
+        read(
+            &mut values,
+            "accepts-transfers",
+            &mut properties.accepts_transfers,
+        );
+        read(&mut values, "allow-flight", &mut properties.allow_flight);
+        read(&mut values, "allow-nether", &mut properties.allow_nether);
+        read(
+            &mut values,
+            "broadcast-console-to-ops",
+            &mut properties.broadcast_console_to_ops,
+        );
+        read(
+            &mut values,
+            "broadcast-rcon-to-ops",
+            &mut properties.broadcast_rcon_to_ops,
+        );
+        read_optional(
+            &mut values,
+            "bug-report-link",
+            &mut properties.bug_report_link,
+        );
+        read(&mut values, "difficulty", &mut properties.difficulty);
+        read(
+            &mut values,
+            "enable-command-block",
+            &mut properties.enable_command_block,
+        );
+        read(
+            &mut values,
+            "enable-jmx-monitoring",
+            &mut properties.enable_jmx_monitoring,
+        );
+        read(&mut values, "enable-query", &mut properties.enable_query);
+        read(&mut values, "enable-rcon", &mut properties.rcon.enabled);
+        read(&mut values, "enable-status", &mut properties.enable_status);
+        read(
+            &mut values,
+            "enforce-secure-profile",
+            &mut properties.enforce_secure_profile,
+        );
+        read(
+            &mut values,
+            "enforce-whitelist",
+            &mut properties.enforce_whitelist,
+        );
+        read(
+            &mut values,
+            "entity-broadcast-range-percentage",
+            &mut properties.entity_broadcast_range_percentage,
+        );
+        read(
+            &mut values,
+            "force-gamemode",
+            &mut properties.force_gamemode,
+        );
+        read(
+            &mut values,
+            "function-permission-level",
+            &mut properties.function_permission_level,
+        );
+        read(&mut values, "gamemode", &mut properties.gamemode);
+        read(
+            &mut values,
+            "generate-structures",
+            &mut properties.generate_structures,
+        );
+        read(
+            &mut values,
+            "generator-settings",
+            &mut properties.generator_settings,
+        );
+        read(&mut values, "hardcore", &mut properties.hardcore);
+        read(
+            &mut values,
+            "hide-online-players",
+            &mut properties.hide_online_players,
+        );
+        read_optional(
+            &mut values,
+            "initial-disabled-packs",
+            &mut properties.initial_disabled_packs,
+        );
+        read_optional(
+            &mut values,
+            "initial-enabled-packs",
+            &mut properties.initial_enabled_packs,
+        );
+        read(&mut values, "level-name", &mut properties.level_name);
+        read_optional(&mut values, "level-seed", &mut properties.level_seed);
+        read(&mut values, "level-type", &mut properties.level_type);
+        read(&mut values, "log-ips", &mut properties.log_ips);
+        read(
+            &mut values,
+            "max-chained-neighbor-updates",
+            &mut properties.max_chained_neighbor_updates,
+        );
+        read(&mut values, "max-players", &mut properties.max_players);
+        read(&mut values, "max-tick-time", &mut properties.max_tick_time);
+        read(
+            &mut values,
+            "max-world-size",
+            &mut properties.max_world_size,
+        );
+        read(&mut values, "motd", &mut properties.motd);
+        read(
+            &mut values,
+            "network-compression-threshold",
+            &mut properties.network_compression_threshold,
+        );
+        read(&mut values, "online-mode", &mut properties.online_mode);
+        read(
+            &mut values,
+            "op-permission-level",
+            &mut properties.op_permission_level,
+        );
+        read(
+            &mut values,
+            "pause-when-empty-seconds",
+            &mut properties.pause_when_empty_seconds,
+        );
+        read(
+            &mut values,
+            "player-idle-timeout",
+            &mut properties.player_idle_timeout,
+        );
+        read(
+            &mut values,
+            "prevent-proxy-connections",
+            &mut properties.prevent_proxy_connections,
+        );
+        read(&mut values, "pvp", &mut properties.pvp);
+        read(&mut values, "query.port", &mut properties.query_port);
+        read(&mut values, "rate-limit", &mut properties.rate_limit);
+        read_optional(&mut values, "rcon.password", &mut properties.rcon.password);
+        read(&mut values, "rcon.port", &mut properties.rcon.port);
+        read(
+            &mut values,
+            "region-file-compression",
+            &mut properties.region_file_compression,
+        );
+        read(
+            &mut values,
+            "require-resource-pack",
+            &mut properties.require_resource_pack,
+        );
+        read_optional(&mut values, "resource-pack", &mut properties.resource_pack);
+        read_optional(
+            &mut values,
+            "resource-pack-id",
+            &mut properties.resource_pack_id,
+        );
+        read_optional(
+            &mut values,
+            "resource-pack-prompt",
+            &mut properties.resource_pack_prompt,
+        );
+        read_optional(
+            &mut values,
+            "resource-pack-sha1",
+            &mut properties.resource_pack_sha1,
+        );
+        read_optional(&mut values, "server-ip", &mut properties.server_ip);
+        read(&mut values, "server-port", &mut properties.server_port);
+        read(
+            &mut values,
+            "simulation-distance",
+            &mut properties.simulation_distance,
+        );
+        read(&mut values, "spawn-animals", &mut properties.spawn_animals);
+        read(
+            &mut values,
+            "spawn-monsters",
+            &mut properties.spawn_monsters,
+        );
+        read(&mut values, "spawn-npcs", &mut properties.spawn_npcs);
+        read(
+            &mut values,
+            "spawn-protection",
+            &mut properties.spawn_protection,
+        );
+        read(
+            &mut values,
+            "sync-chunk-writes",
+            &mut properties.sync_chunk_writes,
+        );
+        read_optional(
+            &mut values,
+            "text-filtering-config",
+            &mut properties.text_filtering_config,
+        );
+        read(
+            &mut values,
+            "text-filtering-version",
+            &mut properties.text_filtering_version,
+        );
+        read(
+            &mut values,
+            "use-native-transport",
+            &mut properties.use_native_transport,
+        );
+        read(&mut values, "view-distance", &mut properties.view_distance);
+        read(&mut values, "white-list", &mut properties.white_list);
+
+        properties.extra = values.into_iter().collect();
+
+        properties
+    }
+}
+
+impl Properties {
+    pub(crate) fn rcon_address(&self) -> String {
+        format!(
+            "{}:{}",
+            self.server_ip
+                .as_deref()
+                .filter(|ip| !ip.is_empty())
+                .unwrap_or("127.0.0.1"),
+            self.rcon.port
+        )
+    }
+}
+
+// Shown instead of secrets to anyone who has not asked to see them.
+pub(crate) const REDACTED: &str = "********";
+
+impl Properties {
+    // Every key with the value it is written as, extra keys included.
+    pub(crate) fn entries(&self) -> Vec<(String, String)> {
+        let mut value = serde_json::to_value(self).expect("failed to serialize properties");
+        let serde_json::Value::Object(properties) = &mut value else {
+            unreachable!("properties always serialize to an object");
+        };
+
+        fields(properties)
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    serde_json::Value::Null => String::new(),
+                    serde_json::Value::String(string) => string.clone(),
+                    value => value.to_string(),
+                };
+
+                (key, value)
+            })
+            .collect()
+    }
+
+    // Only the given keys are checked, a hand edited file may well have values
+    // out of range already, modded servers often raise the view distance.
+    pub(crate) fn validate(&self, keys: &[&str]) -> Result<(), String> {
+        let ranges = [
+            ("view-distance", self.view_distance, 2, 32),
+            ("simulation-distance", self.simulation_distance, 2, 32),
+            ("op-permission-level", self.op_permission_level as u32, 1, 4),
+            (
+                "function-permission-level",
+                self.function_permission_level as u32,
+                1,
+                4,
+            ),
+            (
+                "entity-broadcast-range-percentage",
+                self.entity_broadcast_range_percentage,
+                10,
+                1000,
+            ),
+            ("max-world-size", self.max_world_size, 1, 29_999_984),
+        ];
+
+        for (key, value, min, max) in ranges {
+            if keys.contains(&key) && !(min..=max).contains(&value) {
+                return Err(format!("{key} must be between {min} and {max}"));
+            }
+        }
+
+        // These would be escaped fine, but no real key has them.
+        for key in keys {
+            if key.is_empty() || key.contains(['\r', '\n', '=', ':', '#', '!', ' ']) {
+                return Err(format!("invalid key `{key}`"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // The vanilla keys that give access to the server or vouch for its resource
+    // pack, and anything a mod or plugin names like a credential.
+    pub(crate) fn is_secret(key: &str) -> bool {
+        let key = key.to_lowercase();
+
+        ["rcon.password", "resource-pack-sha1"].contains(&key.as_str())
+            || ["password", "secret", "token"]
+                .iter()
+                .any(|word| key.contains(word))
+    }
+
+    // A secret sent back as the mask, which leaves the secret as it is.
+    pub(crate) fn is_mask(key: &str, value: &serde_json::Value) -> bool {
+        Properties::is_secret(key) && value.as_str() == Some(REDACTED)
+    }
+
+    pub(crate) fn redacted(&self) -> Properties {
+        let mut value = serde_json::to_value(self).expect("failed to serialize properties");
+        let serde_json::Value::Object(properties) = &mut value else {
+            unreachable!("properties always serialize to an object");
+        };
+
+        for (key, value) in fields(properties) {
+            mask(&key, value);
         }
+
+        serde_json::from_value(value).expect("masking keeps every secret a string")
     }
 }
 
-impl RCONProperty {
-    pub(crate) fn new(enabled: bool, password: Option<String>, port: u32) -> RCONProperty {
-        Self {
-            enabled,
-            password,
-            port,
+// The serialized fields under their server.properties keys, with `rcon` and
+// `extra` taken apart. Extra keys come last.
+fn fields(
+    properties: &mut serde_json::Map<String, serde_json::Value>,
+) -> Vec<(String, &mut serde_json::Value)> {
+    let mut fields = vec![];
+    let mut extra_fields = vec![];
+
+    for (field, value) in properties.iter_mut() {
+        match (field.as_str(), value) {
+            ("extra", serde_json::Value::Object(extra)) => {
+                extra_fields.extend(extra.iter_mut().map(|(key, value)| (key.clone(), value)))
+            }
+            ("rcon", serde_json::Value::Object(rcon)) => fields.extend(
+                rcon.iter_mut()
+                    .map(|(name, value)| (key(&format!("rcon.{name}")), value)),
+            ),
+            (field, value) => fields.push((key(field), value)),
+        }
+    }
+
+    fields.append(&mut extra_fields);
+
+    fields
+}
+
+// Unset secrets stay empty, so it can still be told whether there is one.
+fn mask(key: &str, value: &mut serde_json::Value) {
+    if Properties::is_secret(key) && value.as_str().is_some_and(|value| !value.is_empty()) {
+        *value = serde_json::Value::String(REDACTED.to_string());
+    }
+}
+
+// The server.properties key of a field, the ones in `rcon` as `rcon.<name>`.
+pub(crate) fn key(field: &str) -> String {
+    match field {
+        "ip" => "server-ip".to_string(),
+        "port" => "server-port".to_string(),
+        "query_port" => "query.port".to_string(),
+        "whitelist" => "white-list".to_string(),
+        "rcon.enabled" => "enable-rcon".to_string(),
+        "rcon.password" => "rcon.password".to_string(),
+        "rcon.port" => "rcon.port".to_string(),
+        _ => field.replace('_', "-"),
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    // Old servers wrote the numeric ids.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "peaceful" | "0" => Ok(Difficulty::Peaceful),
+            "easy" | "1" => Ok(Difficulty::Easy),
+            "normal" | "2" => Ok(Difficulty::Normal),
+            "hard" | "3" => Ok(Difficulty::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Gamemode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "survival" | "0" => Ok(Gamemode::Survival),
+            "creative" | "1" => Ok(Gamemode::Creative),
+            "adventure" | "2" => Ok(Gamemode::Adventure),
+            "spectator" | "3" => Ok(Gamemode::Spectator),
+            _ => Err(()),
+        }
+    }
+}
+
+fn read<T: FromStr>(values: &mut HashMap<String, String>, key: &str, target: &mut T) {
+    let Some(value) = values.remove(key) else {
+        return;
+    };
+
+    match value.trim().parse() {
+        Ok(value) => *target = value,
+        Err(_) => println!("Invalid value for {key} in server.properties: {value}"),
+    }
+}
+
+// Empty means unset for these.
+fn read_optional(values: &mut HashMap<String, String>, key: &str, target: &mut Option<String>) {
+    if let Some(value) = values.remove(key) {
+        *target = Some(value).filter(|value| !value.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VANILLA: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/vanilla-1.21.properties"
+    ));
+    const FORGE: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/forge-1.12.properties"
+    ));
+
+    #[test]
+    fn a_stock_file_has_no_extra_keys() {
+        let properties = Properties::new(VANILLA);
+
+        assert!(
+            properties.extra.is_empty(),
+            "not modelled: {:?}",
+            properties.extra.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn a_stock_file_reads_back_as_written() {
+        let entries: HashMap<String, String> =
+            Properties::new(VANILLA).entries().into_iter().collect();
+
+        for (key, value) in Document::parse(VANILLA).entries() {
+            assert_eq!(entries.get(key).map(String::as_str), Some(value), "{key}");
+        }
+    }
+
+    #[test]
+    fn missing_keys_keep_the_defaults() {
+        let empty = serde_json::to_value(Properties::new("")).unwrap();
+
+        assert_eq!(empty, serde_json::to_value(Properties::default()).unwrap());
+
+        let properties = Properties::new("motd=Hello\nmax-players=5\n");
+        let defaults = Properties::default();
+
+        assert_eq!(properties.motd, "Hello");
+        assert_eq!(properties.max_players, 5);
+        assert_eq!(properties.view_distance, defaults.view_distance);
+        assert_eq!(properties.rcon.port, 25575);
+        assert_eq!(properties.pause_when_empty_seconds, 60);
+        assert_eq!(properties.region_file_compression, "deflate");
+        assert!(!properties.accepts_transfers);
+        assert!(properties.extra.is_empty());
+    }
+
+    #[test]
+    fn unreadable_values_keep_the_defaults() {
+        let properties = Properties::new("max-players=lots\npvp=maybe\nrcon.port=99999\n");
+
+        assert_eq!(properties.max_players, 20);
+        assert!(properties.pvp);
+        assert_eq!(properties.rcon.port, 25575);
+    }
+
+    #[test]
+    fn old_servers() {
+        let properties = Properties::new(FORGE);
+
+        assert_eq!(properties.difficulty, Difficulty::Easy);
+        assert_eq!(properties.gamemode, Gamemode::Survival);
+        assert_eq!(
+            properties.level_seed.as_deref(),
+            Some("-4172144997902289642")
+        );
+        assert_eq!(properties.rcon.password.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn unknown_keys_are_kept() {
+        let properties = Properties::new("motd=x\nsome-mod.option=on\n");
+
+        assert_eq!(
+            properties.extra.get("some-mod.option").map(String::as_str),
+            Some("on")
+        );
+        assert!(properties
+            .entries()
+            .contains(&("some-mod.option".to_string(), "on".to_string())));
+    }
+
+    #[test]
+    fn keeps_the_shape_clients_read() {
+        let value = serde_json::to_value(Properties::new(
+            "server-ip=10.0.0.2\nserver-port=25566\nwhite-list=true\nenable-rcon=true\nrcon.password=secret\nrcon.port=25580\n",
+        ))
+        .unwrap();
+
+        assert_eq!(value["ip"], "10.0.0.2");
+        assert_eq!(value["port"], 25566);
+        assert_eq!(value["whitelist"], true);
+        assert_eq!(
+            value["rcon"],
+            serde_json::json!({"enabled": true, "password": "secret", "port": 25580})
+        );
+
+        for field in ["max_players", "motd", "online_mode", "pvp", "hardcore"] {
+            assert!(value.get(field).is_some(), "{field}");
+        }
+
+        for field in [
+            "server_ip",
+            "server_port",
+            "white_list",
+            "enable_rcon",
+            "rcon_port",
+        ] {
+            assert!(value.get(field).is_none(), "{field}");
+        }
+    }
+
+    #[test]
+    fn secrets() {
+        for key in [
+            "rcon.password",
+            "resource-pack-sha1",
+            "mymod.api-token",
+            "discord.bot_password",
+            "Backup.Secret",
+            "web.TOKEN",
+        ] {
+            assert!(Properties::is_secret(key), "{key}");
+        }
+
+        for key in [
+            "motd",
+            "rcon.port",
+            "resource-pack",
+            "level-seed",
+            "mymod.color",
+        ] {
+            assert!(!Properties::is_secret(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn redacted_masks_every_secret() {
+        let properties = Properties::new(
+            "rcon.password=hunter2\nresource-pack-sha1=abc123\nresource-pack=https://example.com/pack.zip\n\
+             mymod.api-token=abc\nmymod.db_password=pw\nmymod.color=red\n",
+        )
+        .redacted();
+
+        assert_eq!(properties.rcon.password.as_deref(), Some(REDACTED));
+        assert_eq!(properties.resource_pack_sha1.as_deref(), Some(REDACTED));
+        assert_eq!(
+            properties.resource_pack.as_deref(),
+            Some("https://example.com/pack.zip")
+        );
+        assert_eq!(properties.extra["mymod.api-token"], REDACTED);
+        assert_eq!(properties.extra["mymod.db_password"], REDACTED);
+        assert_eq!(properties.extra["mymod.color"], "red");
+    }
+
+    #[test]
+    fn unset_secrets_stay_empty() {
+        let properties =
+            Properties::new("rcon.password=\nresource-pack-sha1=\nmymod.api-token=\n").redacted();
+
+        assert_eq!(properties.rcon.password, None);
+        assert_eq!(properties.resource_pack_sha1, None);
+        assert_eq!(properties.extra["mymod.api-token"], "");
+    }
+
+    #[test]
+    fn only_the_mask_of_a_secret_is_a_mask() {
+        let mask = serde_json::json!(REDACTED);
+
+        assert!(Properties::is_mask("rcon.password", &mask));
+        assert!(!Properties::is_mask("motd", &mask));
+        assert!(!Properties::is_mask(
+            "rcon.password",
+            &serde_json::json!("hunter2")
+        ));
+    }
+
+    #[test]
+    fn fields_map_to_their_keys() {
+        assert_eq!(key("ip"), "server-ip");
+        assert_eq!(key("port"), "server-port");
+        assert_eq!(key("whitelist"), "white-list");
+        assert_eq!(key("query_port"), "query.port");
+        assert_eq!(key("rcon.enabled"), "enable-rcon");
+        assert_eq!(key("rcon.password"), "rcon.password");
+        assert_eq!(key("rcon.port"), "rcon.port");
+        assert_eq!(key("view_distance"), "view-distance");
+
+        let entries: Vec<String> = Properties::default()
+            .entries()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in ["server-ip", "enable-rcon", "rcon.password", "white-list"] {
+            assert!(entries.contains(&key.to_string()), "{key}");
         }
     }
 }
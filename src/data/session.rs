@@ -0,0 +1,314 @@
+use std::{collections::HashMap, fmt, io, path::PathBuf, sync::RwLock, time::Duration};
+
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::Mutex};
+
+use crate::utils;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read revoked sessions file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse revoked sessions file: {error}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Claims {
+    pub(crate) id: String,
+    pub(crate) user: String,
+    pub(crate) issued_at: i64,
+    pub(crate) expires_at: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Session {
+    pub(crate) access_token: String,
+    pub(crate) issued_at: i64,
+    pub(crate) expires_at: i64,
+}
+
+// Tokens are `<claims>.<signature>`, both base64url encoded. The signature is
+// the HMAC of the encoded claims, so nothing has to be stored to validate one,
+// only revoked tokens are remembered until they would have expired anyway.
+// They are kept on disk, a key from `HABWA_SESSION_KEY` outlives a restart.
+pub(crate) struct Sessions {
+    key: String,
+    lifetime: RwLock<Duration>,
+    path: PathBuf,
+    revoked: Mutex<HashMap<String, i64>>,
+}
+
+impl Sessions {
+    pub(crate) async fn load<T: Into<String>>(
+        key: T,
+        lifetime: Duration,
+        path: PathBuf,
+    ) -> Result<Sessions, Error> {
+        let revoked = match fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(Error::Parse)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(Error::Read(error)),
+        };
+
+        Ok(Self {
+            key: key.into(),
+            lifetime: RwLock::new(lifetime),
+            path,
+            revoked: Mutex::new(revoked),
+        })
+    }
+
+    // Only tokens issued from now on get the new lifetime.
+    pub(crate) fn configure(&self, lifetime: Duration) {
+        *self
+            .lifetime
+            .write()
+            .expect("session lifetime lock poisoned") = lifetime;
+    }
+
+    pub(crate) fn issue(&self, user: &str) -> Session {
+        let issued_at = Utc::now().timestamp();
+        let lifetime = *self
+            .lifetime
+            .read()
+            .expect("session lifetime lock poisoned");
+        let claims = Claims {
+            id: utils::generate_password(24),
+            user: user.to_string(),
+            issued_at,
+            expires_at: issued_at + lifetime.as_secs() as i64,
+        };
+        let payload = general_purpose::URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&claims).expect("failed to serialize session claims"));
+
+        Session {
+            access_token: format!("{payload}.{}", self.sign(&payload)),
+            issued_at: claims.issued_at,
+            expires_at: claims.expires_at,
+        }
+    }
+
+    pub(crate) async fn verify(&self, token: &str) -> Option<Claims> {
+        let (payload, signature) = token.split_once('.')?;
+
+        if !utils::constant_time_eq(self.sign(payload).as_bytes(), signature.as_bytes()) {
+            return None;
+        }
+
+        let claims: Claims =
+            serde_json::from_slice(&general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+        if claims.expires_at <= Utc::now().timestamp()
+            || self.revoked.lock().await.contains_key(&claims.id)
+        {
+            return None;
+        }
+
+        Some(claims)
+    }
+
+    // The token is revoked even when saving fails, it only comes back after
+    // a restart then.
+    pub(crate) async fn revoke(&self, claims: &Claims) -> io::Result<()> {
+        let now = Utc::now().timestamp();
+        let mut revoked = self.revoked.lock().await;
+
+        revoked.retain(|_, expires_at| *expires_at > now);
+        revoked.insert(claims.id.clone(), claims.expires_at);
+
+        let data = serde_json::to_vec(&*revoked).expect("failed to serialize revoked sessions");
+
+        utils::write_atomic(&self.path, data).await
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(hmac_sha256::HMAC::mac(
+            payload.as_bytes(),
+            self.key.as_bytes(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("habwa-sessions-{}-{name}.json", std::process::id()))
+    }
+
+    async fn sessions(name: &str, key: &str, lifetime: Duration) -> Sessions {
+        Sessions::load(key, lifetime, path(name))
+            .await
+            .expect("revoked sessions file")
+    }
+
+    #[tokio::test]
+    async fn issued_tokens_verify() {
+        let sessions = sessions("issue", "key", HOUR).await;
+        let session = sessions.issue("bob");
+        let claims = sessions
+            .verify(&session.access_token)
+            .await
+            .expect("valid token");
+
+        assert_eq!(claims.user, "bob");
+        assert_eq!(claims.issued_at, session.issued_at);
+        assert_eq!(claims.expires_at, session.issued_at + 3600);
+        assert_eq!(session.expires_at, claims.expires_at);
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_rejected() {
+        let sessions = sessions("expiry", "key", Duration::ZERO).await;
+        let session = sessions.issue("bob");
+
+        assert!(sessions.verify(&session.access_token).await.is_none());
+
+        // Tokens already issued keep their lifetime.
+        sessions.configure(HOUR);
+
+        let session = sessions.issue("bob");
+
+        assert!(sessions.verify(&session.access_token).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn tampered_tokens_are_rejected() {
+        let sessions = sessions("tamper", "key", HOUR).await;
+        let token = sessions.issue("bob").access_token;
+        let (payload, signature) = token.split_once('.').unwrap();
+        let mut claims: Claims =
+            serde_json::from_slice(&general_purpose::URL_SAFE_NO_PAD.decode(payload).unwrap())
+                .unwrap();
+
+        claims.user = "alice".to_string();
+
+        let forged = general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+
+        assert!(sessions
+            .verify(&format!("{forged}.{signature}"))
+            .await
+            .is_none());
+        assert!(sessions
+            .verify(&format!("{payload}.{}", sessions.sign(&forged)))
+            .await
+            .is_none());
+        assert!(sessions.verify(payload).await.is_none());
+        assert!(sessions.verify(&format!("{payload}.")).await.is_none());
+
+        // The same token signed with another key.
+        let other = self::sessions("tamper", "other key", HOUR).await;
+
+        assert!(other.verify(&token).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_and_logout_revoke_the_old_token() {
+        let sessions = sessions("revoke", "key", HOUR).await;
+        let old = sessions.issue("bob").access_token;
+        let claims = sessions.verify(&old).await.expect("valid token");
+
+        // A refresh revokes the token it was asked with and issues another.
+        sessions
+            .revoke(&claims)
+            .await
+            .expect("save revoked sessions");
+
+        let new = sessions.issue("bob").access_token;
+
+        assert!(sessions.verify(&old).await.is_none());
+
+        let claims = sessions.verify(&new).await.expect("valid token");
+
+        sessions
+            .revoke(&claims)
+            .await
+            .expect("save revoked sessions");
+
+        assert!(sessions.verify(&new).await.is_none());
+
+        fs::remove_file(path("revoke")).await.expect("remove file");
+    }
+
+    #[tokio::test]
+    async fn revocations_survive_a_restart() {
+        let sessions = sessions("restart", "key", HOUR).await;
+        let revoked = sessions.issue("bob").access_token;
+        let kept = sessions.issue("bob").access_token;
+        let claims = sessions.verify(&revoked).await.expect("valid token");
+
+        sessions
+            .revoke(&claims)
+            .await
+            .expect("save revoked sessions");
+
+        let restarted = self::sessions("restart", "key", HOUR).await;
+
+        assert!(restarted.verify(&revoked).await.is_none());
+        assert!(restarted.verify(&kept).await.is_some());
+
+        fs::remove_file(path("restart")).await.expect("remove file");
+    }
+
+    #[tokio::test]
+    async fn expired_revocations_are_dropped() {
+        let sessions = sessions("prune", "key", HOUR).await;
+        let expired = Claims {
+            id: "expired".to_string(),
+            user: "bob".to_string(),
+            issued_at: 0,
+            expires_at: 1,
+        };
+        let claims = sessions
+            .verify(&sessions.issue("bob").access_token)
+            .await
+            .expect("valid token");
+
+        sessions
+            .revoke(&expired)
+            .await
+            .expect("save revoked sessions");
+        sessions
+            .revoke(&claims)
+            .await
+            .expect("save revoked sessions");
+
+        let revoked: HashMap<String, i64> =
+            serde_json::from_slice(&fs::read(path("prune")).await.expect("read file"))
+                .expect("revoked sessions");
+
+        assert_eq!(revoked.keys().collect::<Vec<_>>(), vec![&claims.id]);
+
+        fs::remove_file(path("prune")).await.expect("remove file");
+    }
+
+    #[tokio::test]
+    async fn bad_files_are_errors() {
+        assert!(matches!(
+            Sessions::load("key", HOUR, std::env::temp_dir()).await,
+            Err(Error::Read(_))
+        ));
+
+        fs::write(path("corrupt"), "{").await.expect("write file");
+
+        assert!(matches!(
+            Sessions::load("key", HOUR, path("corrupt")).await,
+            Err(Error::Parse(_))
+        ));
+
+        fs::remove_file(path("corrupt")).await.expect("remove file");
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, fmt, net::IpAddr, sync::RwLock, time::Duration};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(tag = "kind", content = "key", rename_all = "snake_case")]
+pub(crate) enum Key {
+    Ip(IpAddr),
+    Account(String),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Ip(ip) => write!(f, "ip {ip}"),
+            Key::Account(name) => write!(f, "account {name}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ThrottlePolicy {
+    pub(crate) max_attempts: usize,
+    pub(crate) max_failures: u32,
+    pub(crate) lockout: Duration,
+    pub(crate) max_lockout: Duration,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct Lockout {
+    #[serde(flatten)]
+    pub(crate) key: Key,
+    pub(crate) failures: u32,
+    pub(crate) lockouts: u32,
+    #[serde(with = "crate::data::date_format::user_optional")]
+    pub(crate) locked_until: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Default)]
+struct Entry {
+    attempts: Vec<DateTime<FixedOffset>>,
+    failures: u32,
+    lockouts: u32,
+    locked_until: Option<DateTime<FixedOffset>>,
+    last_seen: Option<DateTime<FixedOffset>>,
+}
+
+// Login attempts are limited per minute for every key, and repeated failures
+// lock the key out for a period that doubles with each lockout.
+pub(crate) struct Throttle {
+    policy: RwLock<ThrottlePolicy>,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl Throttle {
+    pub(crate) fn new(policy: ThrottlePolicy) -> Throttle {
+        Self {
+            policy: RwLock::new(policy),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn configure(&self, policy: ThrottlePolicy) {
+        *self.policy.write().expect("throttle policy lock poisoned") = policy;
+    }
+
+    fn policy(&self) -> ThrottlePolicy {
+        *self.policy.read().expect("throttle policy lock poisoned")
+    }
+
+    // Records an attempt for every key, or returns how long the caller has to
+    // wait when any of them is locked out or over the rate limit.
+    pub(crate) async fn attempt(&self, keys: &[Key]) -> Result<(), Duration> {
+        let now: DateTime<FixedOffset> = Local::now().into();
+        let minute_ago = now - chrono::Duration::minutes(1);
+        let policy = self.policy();
+        let mut entries = self.entries.lock().await;
+        let mut wait = None;
+
+        prune(&mut entries, &policy, now);
+
+        for key in keys {
+            let entry = entries.entry(key.clone()).or_default();
+
+            entry.attempts.retain(|attempt| *attempt > minute_ago);
+
+            if let Some(locked_until) = entry.locked_until.filter(|until| *until > now) {
+                wait = wait.max((locked_until - now).to_std().ok());
+            }
+
+            if entry.attempts.len() >= policy.max_attempts {
+                if let Some(oldest) = entry.attempts.first() {
+                    wait = wait.max((*oldest - minute_ago).to_std().ok());
+                }
+            }
+        }
+
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        for key in keys {
+            let entry = entries.entry(key.clone()).or_default();
+
+            entry.attempts.push(now);
+            entry.last_seen = Some(now);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn failure(&self, keys: &[Key]) {
+        let now: DateTime<FixedOffset> = Local::now().into();
+        let policy = self.policy();
+        let mut entries = self.entries.lock().await;
+
+        for key in keys {
+            let entry = entries.entry(key.clone()).or_default();
+
+            entry.failures += 1;
+            entry.last_seen = Some(now);
+
+            if entry.failures < policy.max_failures {
+                continue;
+            }
+
+            let duration = policy
+                .lockout
+                .saturating_mul(1 << entry.lockouts.min(16))
+                .min(policy.max_lockout);
+
+            entry.failures = 0;
+            entry.lockouts += 1;
+            entry.locked_until = chrono::Duration::from_std(duration)
+                .ok()
+                .map(|duration| now + duration);
+
+            println!("Locked out {key} for {}s", duration.as_secs());
+        }
+    }
+
+    pub(crate) async fn success(&self, keys: &[Key]) {
+        let mut entries = self.entries.lock().await;
+
+        for key in keys {
+            if let Some(entry) = entries.get_mut(key) {
+                entry.failures = 0;
+                entry.lockouts = 0;
+                entry.locked_until = None;
+            }
+        }
+    }
+
+    pub(crate) async fn list(&self) -> Vec<Lockout> {
+        let entries = self.entries.lock().await;
+
+        entries
+            .iter()
+            .filter(|(_, entry)| entry.failures > 0 || entry.lockouts > 0)
+            .map(|(key, entry)| Lockout {
+                key: key.clone(),
+                failures: entry.failures,
+                lockouts: entry.lockouts,
+                locked_until: entry.locked_until,
+            })
+            .collect()
+    }
+
+    pub(crate) async fn clear(&self, key: Option<&Key>) -> usize {
+        let mut entries = self.entries.lock().await;
+
+        match key {
+            Some(key) => entries.remove(key).map_or(0, |_| 1),
+            None => {
+                let count = entries.len();
+
+                entries.clear();
+
+                count
+            }
+        }
+    }
+}
+
+// Anyone can make up account names, so idle entries must not pile up.
+fn prune(entries: &mut HashMap<Key, Entry>, policy: &ThrottlePolicy, now: DateTime<FixedOffset>) {
+    let idle =
+        now - chrono::Duration::from_std(policy.max_lockout).unwrap_or(chrono::Duration::hours(1));
+
+    entries.retain(|_, entry| {
+        entry.locked_until.is_some_and(|until| until > now)
+            || entry.last_seen.is_some_and(|seen| seen > idle)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(max_attempts: usize) -> Throttle {
+        Throttle::new(ThrottlePolicy {
+            max_attempts,
+            max_failures: 2,
+            lockout: Duration::from_secs(10),
+            max_lockout: Duration::from_secs(25),
+        })
+    }
+
+    fn ip(last: u8) -> Key {
+        Key::Ip(IpAddr::from([10, 0, 0, last]))
+    }
+
+    fn account(name: &str) -> Key {
+        Key::Account(name.to_string())
+    }
+
+    // Lets the lockout run out without waiting for it.
+    async fn expire(throttle: &Throttle, key: &Key) {
+        throttle
+            .entries
+            .lock()
+            .await
+            .get_mut(key)
+            .expect("known key")
+            .locked_until = None;
+    }
+
+    async fn lockout(throttle: &Throttle, key: &Key) -> Option<u64> {
+        let entries = throttle.entries.lock().await;
+        let locked_until = entries.get(key)?.locked_until?;
+        let now: DateTime<FixedOffset> = Local::now().into();
+
+        // Rounded up, a little time has passed since it was set.
+        Some((locked_until - now).num_milliseconds() as u64 / 1000 + 1)
+    }
+
+    #[tokio::test]
+    async fn attempts_are_counted_per_key() {
+        let throttle = throttle(2);
+
+        assert!(throttle.attempt(&[ip(1), account("bob")]).await.is_ok());
+        assert!(throttle.attempt(&[ip(1), account("bob")]).await.is_ok());
+
+        // The address is used up with any account, and the account from any
+        // address.
+        let wait = throttle
+            .attempt(&[ip(1), account("alice")])
+            .await
+            .expect_err("address over the limit");
+
+        assert!(wait <= Duration::from_secs(60));
+        assert!(throttle.attempt(&[ip(2), account("bob")]).await.is_err());
+        assert!(throttle.attempt(&[ip(2), account("alice")]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn lockouts_double_up_to_the_cap() {
+        let throttle = throttle(100);
+        let key = ip(1);
+        let keys = [key.clone()];
+
+        for expected in [10, 20, 25, 25] {
+            throttle.failure(&keys).await;
+
+            assert_eq!(lockout(&throttle, &key).await, None);
+            assert!(throttle.attempt(&keys).await.is_ok());
+
+            throttle.failure(&keys).await;
+
+            assert_eq!(lockout(&throttle, &key).await, Some(expected));
+
+            let wait = throttle
+                .attempt(&[key.clone(), account("bob")])
+                .await
+                .expect_err("locked out");
+
+            assert!(
+                wait <= Duration::from_secs(expected) && wait > Duration::from_secs(expected - 2)
+            );
+
+            expire(&throttle, &key).await;
+        }
+
+        let lockouts = throttle.list().await;
+
+        assert_eq!(lockouts.len(), 1);
+        assert_eq!(lockouts[0].key, key);
+        assert_eq!(lockouts[0].lockouts, 4);
+        assert_eq!(lockouts[0].failures, 0);
+    }
+
+    #[tokio::test]
+    async fn success_only_forgives_the_given_keys() {
+        let throttle = throttle(100);
+        let keys = [ip(1), account("bob")];
+
+        throttle.failure(&keys).await;
+        throttle.failure(&keys).await;
+        throttle.success(&[account("bob")]).await;
+
+        assert_eq!(lockout(&throttle, &account("bob")).await, None);
+        assert_eq!(lockout(&throttle, &ip(1)).await, Some(10));
+        assert!(throttle.attempt(&[account("bob")]).await.is_ok());
+        assert!(throttle.attempt(&keys).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn clearing_removes_lockouts() {
+        let throttle = throttle(100);
+
+        throttle.failure(&[ip(1), account("bob")]).await;
+        throttle.failure(&[ip(1), account("bob")]).await;
+        throttle.failure(&[ip(2)]).await;
+
+        assert_eq!(throttle.list().await.len(), 3);
+        assert_eq!(throttle.clear(Some(&account("bob"))).await, 1);
+        assert_eq!(throttle.clear(Some(&account("bob"))).await, 0);
+        assert!(throttle.attempt(&[account("bob")]).await.is_ok());
+        assert!(throttle.attempt(&[ip(1)]).await.is_err());
+        assert_eq!(throttle.clear(None).await, 3);
+        assert!(throttle.list().await.is_empty());
+        assert!(throttle.attempt(&[ip(1)]).await.is_ok());
+    }
+}
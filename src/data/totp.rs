@@ -0,0 +1,476 @@
+use std::{collections::HashMap, fmt, io, path::PathBuf};
+
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tokio::{
+    fs,
+    sync::{Mutex, RwLock},
+};
+
+use crate::utils;
+
+const ISSUER: &str = "Habwa";
+const PERIOD: i64 = 30;
+const RECOVERY_CODES: usize = 10;
+const CHALLENGE_LIFETIME: i64 = 300;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read totp file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse totp file: {error}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+struct Enrollment {
+    // base64 of the raw secret, the otpauth uri carries it as base32.
+    secret: String,
+    enabled: bool,
+    // Hashes of the unused recovery codes.
+    recovery_codes: Vec<String>,
+    // Every code is accepted only once.
+    last_step: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Enrolled {
+    pub(crate) secret: String,
+    pub(crate) uri: String,
+    pub(crate) recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Challenge {
+    pub(crate) totp_required: bool,
+    pub(crate) challenge_token: String,
+    pub(crate) expires_at: i64,
+}
+
+// RFC 6238 codes with SHA-1, 6 digits and 30 second steps, the parameters
+// every authenticator app understands.
+pub(crate) struct Totp {
+    path: PathBuf,
+    enrollments: RwLock<HashMap<String, Enrollment>>,
+    // Logins that got the password right and still owe a code.
+    challenges: Mutex<HashMap<String, (String, i64)>>,
+}
+
+impl Totp {
+    pub(crate) async fn load(path: PathBuf) -> Result<Totp, Error> {
+        let enrollments = match fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(Error::Parse)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(Error::Read(error)),
+        };
+
+        Ok(Self {
+            path,
+            enrollments: RwLock::new(enrollments),
+            challenges: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) async fn is_enabled(&self, user: &str) -> bool {
+        self.enrollments
+            .read()
+            .await
+            .get(user)
+            .is_some_and(|enrollment| enrollment.enabled)
+    }
+
+    // Starts over with a new secret, which only takes effect once a code
+    // from it is confirmed. Returns `None` when totp is already enabled.
+    pub(crate) async fn enroll(&self, user: &str) -> io::Result<Option<Enrolled>> {
+        let mut enrollments = self.enrollments.write().await;
+
+        if enrollments
+            .get(user)
+            .is_some_and(|enrollment| enrollment.enabled)
+        {
+            return Ok(None);
+        }
+
+        let secret: [u8; 20] = rand::random();
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODES)
+            .map(|_| {
+                let code = utils::generate_password(10).to_lowercase();
+
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+
+        enrollments.insert(
+            user.to_string(),
+            Enrollment {
+                secret: general_purpose::STANDARD.encode(secret),
+                enabled: false,
+                recovery_codes: recovery_codes
+                    .iter()
+                    .map(|code| utils::digest(&normalize(code)))
+                    .collect(),
+                last_step: 0,
+            },
+        );
+        self.save(&enrollments).await?;
+
+        let secret = base32(&secret);
+        let label = percent_encode(&format!("{ISSUER}:{user}"));
+
+        Ok(Some(Enrolled {
+            uri: format!(
+                "otpauth://totp/{label}?secret={secret}&issuer={ISSUER}&algorithm=SHA1&digits=6&period={PERIOD}"
+            ),
+            secret,
+            recovery_codes,
+        }))
+    }
+
+    pub(crate) async fn confirm(&self, user: &str, code: &str) -> io::Result<bool> {
+        let mut enrollments = self.enrollments.write().await;
+        let Some(enrollment) = enrollments.get_mut(user) else {
+            return Ok(false);
+        };
+
+        if enrollment.enabled || !check_code(enrollment, code) {
+            return Ok(false);
+        }
+
+        enrollment.enabled = true;
+        self.save(&enrollments).await?;
+
+        Ok(true)
+    }
+
+    pub(crate) async fn disable(&self, user: &str) -> io::Result<bool> {
+        let mut enrollments = self.enrollments.write().await;
+
+        if enrollments.remove(user).is_none() {
+            return Ok(false);
+        }
+
+        self.save(&enrollments).await?;
+
+        Ok(true)
+    }
+
+    // Accepts a current code or one of the recovery codes, which is used up.
+    pub(crate) async fn verify(&self, user: &str, code: &str) -> io::Result<bool> {
+        let mut enrollments = self.enrollments.write().await;
+        let Some(enrollment) = enrollments.get_mut(user).filter(|e| e.enabled) else {
+            return Ok(false);
+        };
+
+        if !check_code(enrollment, code) {
+            let hash = utils::digest(&normalize(code));
+            let count = enrollment.recovery_codes.len();
+
+            enrollment
+                .recovery_codes
+                .retain(|stored| !utils::constant_time_eq(stored.as_bytes(), hash.as_bytes()));
+
+            if enrollment.recovery_codes.len() == count {
+                return Ok(false);
+            }
+
+            println!(
+                "{user} used a recovery code, {} left",
+                enrollment.recovery_codes.len()
+            );
+        }
+
+        self.save(&enrollments).await?;
+
+        Ok(true)
+    }
+
+    pub(crate) async fn challenge(&self, user: &str) -> Challenge {
+        let now = Utc::now().timestamp();
+        let token = utils::generate_password(40);
+        let expires_at = now + CHALLENGE_LIFETIME;
+        let mut challenges = self.challenges.lock().await;
+
+        challenges.retain(|_, (_, expires_at)| *expires_at > now);
+        challenges.insert(token.clone(), (user.to_string(), expires_at));
+
+        Challenge {
+            totp_required: true,
+            challenge_token: token,
+            expires_at,
+        }
+    }
+
+    // The user a pending challenge belongs to, it stays pending so a mistyped
+    // code can be retried.
+    pub(crate) async fn challenged(&self, token: &str) -> Option<String> {
+        let now = Utc::now().timestamp();
+
+        self.challenges
+            .lock()
+            .await
+            .get(token)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(user, _)| user.clone())
+    }
+
+    pub(crate) async fn complete(&self, token: &str) {
+        self.challenges.lock().await.remove(token);
+    }
+
+    async fn save(&self, enrollments: &HashMap<String, Enrollment>) -> io::Result<()> {
+        let data =
+            serde_json::to_vec_pretty(enrollments).expect("failed to serialize totp enrollments");
+
+        utils::write_private(&self.path, data).await
+    }
+}
+
+// Allows one step of clock drift either way.
+fn check_code(enrollment: &mut Enrollment, code: &str) -> bool {
+    let Ok(secret) = general_purpose::STANDARD.decode(&enrollment.secret) else {
+        return false;
+    };
+    let code = code.trim();
+    let now = Utc::now().timestamp() / PERIOD;
+
+    for step in (now - 1)..=(now + 1) {
+        if step <= enrollment.last_step {
+            continue;
+        }
+
+        if utils::constant_time_eq(generate(&secret, step).as_bytes(), code.as_bytes()) {
+            enrollment.last_step = step;
+
+            return true;
+        }
+    }
+
+    false
+}
+
+fn generate(secret: &[u8], step: i64) -> String {
+    let mac = hmac_sha1(secret, &step.to_be_bytes());
+    let offset = (mac[19] & 0xf) as usize;
+    let value = u32::from_be_bytes([
+        mac[offset] & 0x7f,
+        mac[offset + 1],
+        mac[offset + 2],
+        mac[offset + 3],
+    ]);
+
+    format!("{:06}", value % 1_000_000)
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let mut block = [0; 64];
+
+    if key.len() > block.len() {
+        block[..20].copy_from_slice(&Sha1::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha1::new()
+        .chain_update(block.map(|byte| byte ^ 0x36))
+        .chain_update(message)
+        .finalize();
+
+    Sha1::new()
+        .chain_update(block.map(|byte| byte ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn normalize(code: &str) -> String {
+    code.trim().replace('-', "").to_lowercase()
+}
+
+// RFC 4648 without padding, as authenticator apps expect the secret.
+fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut output = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        output.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    output
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    // RFC 2202 section 3, the last one has a key longer than a block.
+    #[test]
+    fn hmac_sha1_matches_rfc_2202() {
+        assert_eq!(
+            hex(&hmac_sha1(&[0x0b; 20], b"Hi There")),
+            "b617318655057264e28bc0b6fb378c8ef146be00"
+        );
+        assert_eq!(
+            hex(&hmac_sha1(b"Jefe", b"what do ya want for nothing?")),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            hex(&hmac_sha1(
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112"
+        );
+    }
+
+    // RFC 4226 appendix D.
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(generate(RFC_SECRET, counter as i64), *code);
+        }
+    }
+
+    // RFC 6238 appendix B for SHA-1, which lists 8 digits, the last 6 of them.
+    #[test]
+    fn totp_matches_rfc_6238() {
+        let expected = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        for (time, code) in expected {
+            assert_eq!(generate(RFC_SECRET, time / PERIOD), code);
+        }
+    }
+
+    // RFC 4648 section 10, without the padding.
+    #[test]
+    fn base32_matches_rfc_4648() {
+        let expected = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+
+        for (data, encoded) in expected {
+            assert_eq!(base32(data.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn codes_are_accepted_once_within_the_window() {
+        let mut enrollment = Enrollment {
+            secret: general_purpose::STANDARD.encode(RFC_SECRET),
+            enabled: true,
+            recovery_codes: vec![],
+            last_step: 0,
+        };
+        let now = Utc::now().timestamp() / PERIOD;
+
+        assert!(!check_code(&mut enrollment, &generate(RFC_SECRET, now - 2)));
+        assert!(check_code(&mut enrollment, &generate(RFC_SECRET, now - 1)));
+        assert!(!check_code(&mut enrollment, &generate(RFC_SECRET, now - 1)));
+        assert!(check_code(
+            &mut enrollment,
+            &format!(" {} ", generate(RFC_SECRET, now))
+        ));
+        assert!(!check_code(&mut enrollment, &generate(RFC_SECRET, now)));
+        assert!(!check_code(&mut enrollment, &generate(RFC_SECRET, now + 2)));
+    }
+
+    #[tokio::test]
+    async fn enrollments_are_saved_privately() {
+        let path = std::env::temp_dir().join(format!("habwa-totp-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path).await;
+        let totp = Totp::load(path.clone()).await.expect("no totp file");
+
+        assert!(totp.enroll("bob").await.expect("save totp").is_some());
+
+        let metadata = fs::metadata(&path).await.expect("read metadata");
+
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        let loaded = Totp::load(path.clone()).await.expect("totp file");
+
+        assert!(loaded.enrollments.read().await.contains_key("bob"));
+
+        fs::remove_file(&path).await.expect("remove totp file");
+    }
+
+    #[tokio::test]
+    async fn bad_files_are_errors() {
+        let path =
+            std::env::temp_dir().join(format!("habwa-totp-corrupt-{}.json", std::process::id()));
+
+        assert!(matches!(
+            Totp::load(std::env::temp_dir()).await,
+            Err(Error::Read(_))
+        ));
+
+        fs::write(&path, "{\"bob\":")
+            .await
+            .expect("write totp file");
+
+        assert!(matches!(
+            Totp::load(path.clone()).await,
+            Err(Error::Parse(_))
+        ));
+
+        fs::remove_file(&path).await.expect("remove totp file");
+    }
+}
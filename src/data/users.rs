@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fmt, io, path::Path, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::{api_keys::Scope, role::Role, session::Claims};
+use crate::utils;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Read(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(error) => write!(f, "failed to read users file: {error}"),
+            Error::Parse(error) => write!(f, "failed to parse users file: {error}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub(crate) struct User {
+    // Output of the `hash-password` subcommand.
+    pub(crate) password: String,
+    pub(crate) role: Role,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct Account {
+    pub(crate) name: String,
+    pub(crate) role: Role,
+    // Only api keys are limited to scopes, users get whatever their role allows.
+    pub(crate) scopes: Option<Vec<Scope>>,
+    #[serde(skip)]
+    pub(crate) session: Option<Claims>,
+}
+
+impl Account {
+    pub(crate) fn allows(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+}
+
+// Replaced as a whole when the config file is reloaded.
+pub(crate) struct Users {
+    users: RwLock<HashMap<String, User>>,
+}
+
+impl Users {
+    pub(crate) fn new(users: HashMap<String, User>) -> Users {
+        Self {
+            users: RwLock::new(users),
+        }
+    }
+
+    pub(crate) async fn load(path: &Path) -> Result<HashMap<String, User>, Error> {
+        let data = fs::read_to_string(path).await.map_err(Error::Read)?;
+
+        toml::from_str(&data).map_err(Error::Parse)
+    }
+
+    pub(crate) fn replace(&self, users: HashMap<String, User>) {
+        *self.users.write().expect("users lock poisoned") = users;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.users.read().expect("users lock poisoned").is_empty()
+    }
+
+    pub(crate) fn account(&self, name: &str) -> Option<Account> {
+        let users = self.users.read().expect("users lock poisoned");
+
+        users.get(name).map(|user| Account {
+            name: name.to_string(),
+            role: user.role,
+            scopes: None,
+            session: None,
+        })
+    }
+
+    pub(crate) fn verify(&self, name: &str, password: &str) -> Option<Account> {
+        // Cloned so the lock is not held through the slow hash.
+        let user = self
+            .users
+            .read()
+            .expect("users lock poisoned")
+            .get(name)
+            .cloned();
+
+        let Some(user) = user else {
+            utils::verify_password(password, utils::DUMMY_HASH);
+
+            return None;
+        };
+
+        if !utils::verify_password(password, &user.password) {
+            return None;
+        }
+
+        self.account(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn load(name: &str, data: &str) -> Result<HashMap<String, User>, Error> {
+        let path =
+            std::env::temp_dir().join(format!("habwa-users-{}-{name}.toml", std::process::id()));
+
+        fs::write(&path, data).await.expect("write users file");
+
+        let users = Users::load(&path).await;
+
+        fs::remove_file(&path).await.expect("remove users file");
+
+        users
+    }
+
+    #[tokio::test]
+    async fn loads_users() {
+        let users = load(
+            "valid",
+            "[bob]\npassword = \"hash\"\nrole = \"admin\"\n\n[alice]\npassword = \"hash\"\nrole = \"viewer\"\n",
+        )
+        .await
+        .expect("valid users file");
+
+        assert_eq!(users["bob"].role, Role::Admin);
+        assert_eq!(users["alice"].role, Role::Viewer);
+    }
+
+    #[tokio::test]
+    async fn bad_files_are_errors() {
+        assert!(matches!(
+            Users::load(Path::new("/nonexistent/users.toml")).await,
+            Err(Error::Read(_))
+        ));
+        assert!(matches!(
+            load("syntax", "[bob\npassword = \"hash\"").await,
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            load("role", "[bob]\npassword = \"hash\"\nrole = \"owner\"").await,
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            load("password", "[bob]\nrole = \"admin\"").await,
+            Err(Error::Parse(_))
+        ));
+    }
+}
@@ -1,12 +1,40 @@
-use std::{collections::HashMap, io::Cursor, path::PathBuf};
-
-use async_zip::{base::read::seek::ZipFileReader, ZipFile};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::{self, Cursor},
+    path::PathBuf,
+};
+
+use async_zip::{base::read::seek::ZipFileReader, error::ZipError, ZipFile};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use toml::Value;
 
 use crate::parsers;
 
+#[derive(Debug)]
+pub(crate) enum Error {
+    Open(io::Error),
+    Archive(ZipError),
+    Manifest(toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Open(error) => write!(f, "failed to open mod file: {error}"),
+            Error::Archive(error) => write!(f, "failed to read mod file: {error}"),
+            Error::Manifest(error) => write!(f, "failed to parse mods.toml: {error}"),
+        }
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(error: ZipError) -> Self {
+        Error::Archive(error)
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub(crate) struct ModManifest {
     #[serde(rename = "modLoader")]
@@ -57,6 +85,12 @@ pub(crate) struct Mod {
     #[serde(default = "default_display_test")]
     pub(crate) display_test: ModDisplayTest,
     pub(crate) dependencies: Option<Vec<ModDependency>>,
+    #[serde(skip_deserializing)]
+    pub(crate) file: Option<String>,
+    #[serde(skip)]
+    pub(crate) packages: Vec<String>,
+    #[serde(skip)]
+    pub(crate) mixins: Vec<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -158,31 +192,63 @@ fn archive_contains(archive: &ZipFile, files: Vec<&str>) -> bool {
     exists
 }
 
+fn archive_classes(archive: &ZipFile) -> (Vec<String>, Vec<String>) {
+    let mut packages = BTreeSet::new();
+    let mut mixins = BTreeSet::new();
+
+    for stored_entry in archive.entries() {
+        let Ok(filename) = stored_entry.entry().filename().clone().into_string() else {
+            continue;
+        };
+
+        if filename.starts_with("META-INF/") {
+            continue;
+        }
+
+        if let Some(class) = filename.strip_suffix(".class") {
+            if let Some((package, _)) = class.rsplit_once('/') {
+                packages.insert(package.replace('/', "."));
+            }
+        } else if filename.ends_with(".json")
+            && filename.contains("mixin")
+            && !filename.contains('/')
+        {
+            mixins.insert(filename);
+        }
+    }
+
+    (packages.into_iter().collect(), mixins.into_iter().collect())
+}
+
 pub(crate) async fn is_mod(data: &[u8]) -> bool {
-    let reader = ZipFileReader::with_tokio(Cursor::new(data))
-        .await
-        .expect("failed to read mod file");
+    let Ok(reader) = ZipFileReader::with_tokio(Cursor::new(data)).await else {
+        return false;
+    };
     let archive = reader.file();
 
-    archive_contains(
-        archive,
-        vec!["META-INF/MANIFEST.MF", "META-INF/mods.toml"],
-    )
+    archive_contains(archive, vec!["META-INF/MANIFEST.MF", "META-INF/mods.toml"])
 }
 
-pub(crate) async fn load_mod_by_path(path: PathBuf) -> Vec<Mod> {
-    let file = fs::File::open(path).await.expect("failed to open mod file");
+pub(crate) async fn load_mod_by_path(path: PathBuf) -> Result<Vec<Mod>, Error> {
+    let filename = path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_string());
+    let file = fs::File::open(path).await.map_err(Error::Open)?;
+    let mut mods = load_mod(file).await?;
+
+    for r#mod in &mut mods {
+        r#mod.file = filename.clone();
+    }
 
-    load_mod(file).await
+    Ok(mods)
 }
 
 pub(crate) async fn load_mod<R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin>(
     data: R,
-) -> Vec<Mod> {
-    let mut reader = ZipFileReader::with_tokio(data)
-        .await
-        .expect("failed to read mod file");
+) -> Result<Vec<Mod>, Error> {
+    let mut reader = ZipFileReader::with_tokio(data).await?;
     let archive = reader.file();
+    let (packages, mixins) = archive_classes(archive);
     let files = archive.entries();
     let mut mods = Vec::new();
 
@@ -203,27 +269,22 @@ pub(crate) async fn load_mod<R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Un
     });
 
     if let Some(index) = toml_index {
-        let mut data = reader.reader_with_entry(index).await.unwrap();
+        let mut data = reader.reader_with_entry(index).await?;
         let mut buffer = String::new();
 
-        data.read_to_string_checked(&mut buffer)
-            .await
-            .expect("failed to read a mods.toml inside mod file");
+        data.read_to_string_checked(&mut buffer).await?;
 
-        let manifest: ModManifest =
-            toml::from_str(&buffer).expect("failed to deserialize mods.toml buffer");
+        let manifest: ModManifest = toml::from_str(&buffer).map_err(Error::Manifest)?;
 
         if let Some(dependencies) = manifest.dependencies {
             for mut r#mod in manifest.mods {
                 r#mod.dependencies = dependencies.get(&r#mod.mod_id).cloned();
 
                 if let Some(index) = manifest_index {
-                    let mut data = reader.reader_with_entry(index).await.unwrap();
+                    let mut data = reader.reader_with_entry(index).await?;
                     let mut buffer = String::new();
 
-                    data.read_to_string_checked(&mut buffer)
-                        .await
-                        .expect("failed to read a MANIFEST.MF inside mod file");
+                    data.read_to_string_checked(&mut buffer).await?;
 
                     let manifest = parsers::manifest::parse(buffer);
 
@@ -239,12 +300,10 @@ pub(crate) async fn load_mod<R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Un
         } else {
             for mut r#mod in manifest.mods {
                 if let Some(index) = manifest_index {
-                    let mut data = reader.reader_with_entry(index).await.unwrap();
+                    let mut data = reader.reader_with_entry(index).await?;
                     let mut buffer = String::new();
 
-                    data.read_to_string_checked(&mut buffer)
-                        .await
-                        .expect("failed to read a MANIFEST.MF inside mod file");
+                    data.read_to_string_checked(&mut buffer).await?;
 
                     let manifest = parsers::manifest::parse(buffer);
 
@@ -260,5 +319,10 @@ pub(crate) async fn load_mod<R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Un
         }
     }
 
-    mods
+    for r#mod in &mut mods {
+        r#mod.packages = packages.clone();
+        r#mod.mixins = mixins.clone();
+    }
+
+    Ok(mods)
 }
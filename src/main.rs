@@ -1,36 +1,65 @@
+pub(crate) mod analyzer;
+pub(crate) mod config;
+pub(crate) mod console;
 pub(crate) mod data;
 pub(crate) mod loaders;
 pub(crate) mod parsers;
+pub(crate) mod rcon;
 pub(crate) mod routes;
+pub(crate) mod supervisor;
+pub(crate) mod tls;
+pub(crate) mod transport;
 pub(crate) mod utils;
+pub(crate) mod watcher;
 
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    http::{header, HeaderValue, Method},
+    middleware,
+    routing::{delete, get, patch, post},
     Router,
 };
 use axum_server::tls_rustls::RustlsConfig;
-use clap::{arg, command, Parser};
+use clap::{arg, command, Parser, Subcommand};
+use config::Config;
 use core::panic;
-use data::app;
+use data::{
+    api_keys::{ApiKeys, Scope},
+    app,
+    audit::AuditLog,
+    commands::CommandPolicy,
+    role::Role,
+    session::Sessions,
+    throttle::Throttle,
+    totp::Totp,
+    users::{User, Users},
+};
 use std::{
-    net::SocketAddr,
+    collections::HashMap,
+    env, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use tokio::fs;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+// Flags left out fall back to the config file, and then to its defaults.
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    server_path: String,
+    #[command(subcommand)]
+    action: Option<Action>,
 
-    #[arg(short, long)]
-    password: Option<String>,
+    #[arg(required = true)]
+    server_path: Option<String>,
 
-    #[arg(short, long)]
-    encryption_key: Option<String>,
+    #[arg(long)]
+    config: Option<String>,
+
+    #[arg(long)]
+    password_file: Option<String>,
 
     #[arg(short, long)]
     ssl: bool,
@@ -41,16 +70,175 @@ struct Args {
     #[arg(short = 'c', long)]
     ssl_cert: Option<String>,
 
-    #[arg(long, default_value_t = 3000)]
-    port: u16,
+    #[arg(long)]
+    bind: Option<IpAddr>,
+
+    #[arg(long)]
+    port: Option<u16>,
+
+    #[arg(long)]
+    start_command: Option<String>,
+
+    #[arg(long)]
+    stop_timeout: Option<u64>,
+
+    #[arg(long)]
+    autostart: bool,
+
+    #[arg(long)]
+    no_crash_restart: bool,
+
+    #[arg(long)]
+    crash_backoff: Option<u64>,
+
+    #[arg(long)]
+    crash_backoff_max: Option<u64>,
+
+    #[arg(long)]
+    crash_retries: Option<usize>,
+
+    #[arg(long)]
+    crash_window: Option<u64>,
+
+    #[arg(long)]
+    provision_rcon: bool,
+
+    #[arg(long)]
+    session_lifetime: Option<u64>,
+
+    #[arg(long)]
+    users: Option<String>,
+
+    #[arg(long)]
+    data_dir: Option<String>,
+
+    #[arg(long)]
+    login_attempts: Option<usize>,
+
+    #[arg(long)]
+    login_failures: Option<u32>,
+
+    #[arg(long)]
+    login_lockout: Option<u64>,
+
+    #[arg(long)]
+    login_lockout_max: Option<u64>,
+
+    #[arg(long)]
+    rcon_timeout: Option<u64>,
+
+    #[arg(long)]
+    command_policy: Option<String>,
+
+    #[arg(long)]
+    insecure_no_auth: bool,
+}
+
+impl Args {
+    fn apply(&self, config: &mut Config) {
+        let limits = &mut config.limits;
+
+        if let Some(bind) = self.bind {
+            config.bind = bind;
+        }
+
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+
+        if let Some(start_command) = &self.start_command {
+            config.start_command = start_command.clone();
+        }
+
+        if self.ssl {
+            config.tls.enabled = true;
+        }
+
+        if let Some(cert) = &self.ssl_cert {
+            config.tls.cert = Some(PathBuf::from(cert));
+        }
+
+        if let Some(key) = &self.ssl_key {
+            config.tls.key = Some(PathBuf::from(key));
+        }
+
+        if self.no_crash_restart {
+            limits.crash_restart = false;
+        }
+
+        if let Some(stop_timeout) = self.stop_timeout {
+            limits.stop_timeout = stop_timeout;
+        }
+
+        if let Some(rcon_timeout) = self.rcon_timeout {
+            limits.rcon_timeout = rcon_timeout;
+        }
+
+        if let Some(session_lifetime) = self.session_lifetime {
+            limits.session_lifetime = session_lifetime;
+        }
+
+        if let Some(login_attempts) = self.login_attempts {
+            limits.login_attempts = login_attempts;
+        }
+
+        if let Some(login_failures) = self.login_failures {
+            limits.login_failures = login_failures;
+        }
+
+        if let Some(login_lockout) = self.login_lockout {
+            limits.login_lockout = login_lockout;
+        }
+
+        if let Some(login_lockout_max) = self.login_lockout_max {
+            limits.login_lockout_max = login_lockout_max;
+        }
+
+        if let Some(crash_backoff) = self.crash_backoff {
+            limits.crash_backoff = crash_backoff;
+        }
+
+        if let Some(crash_backoff_max) = self.crash_backoff_max {
+            limits.crash_backoff_max = crash_backoff_max;
+        }
+
+        if let Some(crash_retries) = self.crash_retries {
+            limits.crash_retries = crash_retries;
+        }
+
+        if let Some(crash_window) = self.crash_window {
+            limits.crash_window = crash_window;
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Action {
+    /// Reads a password from stdin and prints the hash to put in a users or password file
+    HashPassword,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
     let args = Args::parse();
 
+    if let Some(Action::HashPassword) = args.action {
+        return hash_password();
+    }
+
+    let server_path = args.server_path.clone().expect("missing server path");
+
+    let mut config = match &args.config {
+        Some(path) => Config::load(Path::new(path))
+            .await
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?,
+        None => Config::default(),
+    };
+
+    args.apply(&mut config);
+
     if !utils::scan_folder(
-        args.server_path.clone(),
+        server_path.clone(),
         vec![
             "config",
             "libraries",
@@ -63,7 +251,11 @@ async fn main() -> Result<(), std::io::Error> {
         panic!("Invalid server path!")
     }
 
-    let server_path = Path::new(&args.server_path).to_path_buf();
+    let server_path = Path::new(&server_path).to_path_buf();
+
+    if args.provision_rcon && utils::provision_rcon(&server_path).await? {
+        println!("Enabled rcon in server.properties");
+    }
 
     let server_properties = {
         let file = fs::read_to_string(server_path.join("server.properties")).await?;
@@ -71,81 +263,508 @@ async fn main() -> Result<(), std::io::Error> {
         data::server::Properties::new(file)
     };
 
-    let client = utils::connect_rcon(
-        server_properties.clone().ip,
-        server_properties.clone().rcon.port,
-        server_properties.clone().rcon.password,
+    let client = rcon::Client::new(
+        server_properties.rcon_address(),
+        server_properties.rcon.password.clone(),
+        Duration::from_secs(config.limits.rcon_timeout),
+    );
+
+    // Accounts from the users file and the admin password are kept apart from
+    // the ones in the config file, which can be reloaded.
+    let mut extra_users = match &args.users {
+        Some(path) => Users::load(Path::new(path))
+            .await
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?,
+        None => HashMap::new(),
+    };
+
+    // Secrets are never taken from the command line, where they would show
+    // up in the process list and shell history.
+    let password_hash = match &args.password_file {
+        Some(path) => Some(fs::read_to_string(path).await?.trim().to_string()),
+        None => env::var("HABWA_PASSWORD_HASH").ok(),
+    };
+
+    if let Some(password_hash) = password_hash {
+        extra_users.insert(
+            "admin".to_string(),
+            User {
+                password: password_hash,
+                role: Role::Admin,
+            },
+        );
+    }
+
+    let data_dir = match &args.data_dir {
+        Some(path) => PathBuf::from(path),
+        None => server_path.join(".habwa"),
+    };
+
+    fs::create_dir_all(&data_dir).await?;
+
+    let mods = utils::load_mods(server_path.clone())
+        .await
+        .unwrap_or_else(|error| {
+            println!("Failed to read the mods folder: {error}");
+
+            vec![]
+        });
+    let user_cache = utils::load_user_cache(&server_path)
+        .await
+        .unwrap_or_else(|error| {
+            println!("Failed to read usercache.json: {error}");
+
+            vec![]
+        });
+
+    let commands = match &args.command_policy {
+        Some(path) => CommandPolicy::load(Path::new(path))
+            .await
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?,
+        None => CommandPolicy::default(),
+    };
+
+    let api_keys = ApiKeys::load(data_dir.join("api_keys.json"))
+        .await
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+    let totp = Totp::load(data_dir.join("totp.json"))
+        .await
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+    let sessions = Sessions::load(
+        env::var("HABWA_SESSION_KEY").unwrap_or_else(|_| utils::generate_password(64)),
+        Duration::from_secs(config.limits.session_lifetime),
+        data_dir.join("revoked_sessions.json"),
     )
-    .await;
+    .await
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+    let mut users = config.users.clone();
 
-    let mut access_token = None;
+    users.extend(extra_users.clone());
 
-    if let Some(password) = args.password {
-        if let Some(encryption_key) = args.encryption_key.clone() {
-            access_token = Some(utils::encode_password(password, encryption_key));
+    // Without an account anyone who reaches the port is an admin, so that has
+    // to be asked for and is kept off the network.
+    if users.is_empty() {
+        if !args.insecure_no_auth {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no accounts configured, add users to the config or --users file, set --password-file or HABWA_PASSWORD_HASH, or pass --insecure-no-auth",
+            ));
         }
-    }
 
-    let mods = utils::load_mods(server_path.clone()).await;
+        config.bind = loopback(config.bind);
+
+        println!("WARNING: running without authentication, every request is treated as an admin");
+        println!(
+            "WARNING: only listening on {} because of --insecure-no-auth",
+            config.bind
+        );
+    }
 
     let state = Arc::new(app::State::new(
         client,
-        server_path,
+        server_path.clone(),
         server_properties,
-        access_token.as_ref(),
-        args.encryption_key.as_ref(),
+        Users::new(users),
         mods,
+        user_cache,
+        supervisor::Supervisor::new(
+            server_path,
+            config.start_command.clone(),
+            Duration::from_secs(config.limits.stop_timeout),
+            config.limits.crash(),
+            console::Console::new(),
+        ),
+        commands,
+        sessions,
+        Throttle::new(config.limits.throttle()),
+        api_keys,
+        AuditLog::new(data_dir.join("audit.jsonl")),
+        totp,
+        config.cors.clone(),
+        config.ip_filter.clone(),
+        args.insecure_no_auth,
     ));
 
-    let app = Router::new()
+    tokio::spawn(supervisor::watchdog(state.clone()));
+    tokio::spawn(console::tail(state.clone()));
+    tokio::spawn(rcon::monitor(state.clone()));
+    tokio::spawn(watcher::watch(state.clone()));
+
+    if let Some(path) = &args.config {
+        let args = args.clone();
+
+        tokio::spawn(config::watch(
+            state.clone(),
+            PathBuf::from(path),
+            config.clone(),
+            extra_users,
+            move |config| args.apply(config),
+        ));
+    }
+
+    if args.autostart {
+        state
+            .supervisor
+            .start()
+            .await
+            .expect("failed to start server");
+    }
+
+    let require = |role: Role, scope: Scope| {
+        middleware::from_fn_with_state((role, scope), routes::access::require)
+    };
+    let audit = |action: &'static str| {
+        middleware::from_fn_with_state((state.clone(), action), routes::audit::record)
+    };
+
+    let public = Router::new()
         .route("/auth", post(routes::auth::execute))
-        .route("/server/config", get(routes::server::config::execute))
-        .route("/server/mods", get(routes::server::mods::execute))
-        .route("/server/mods/upload", post(routes::server::mods::upload))
-        .route("/server/players", get(routes::server::players::execute))
-        .route("/server/restart", get(routes::server::restart::execute))
+        .route("/auth/totp/verify", post(routes::totp::verify));
+
+    let protected = Router::new()
+        .route(
+            "/audit",
+            get(routes::audit::execute).route_layer(require(Role::Admin, Scope::Admin)),
+        )
+        .route(
+            "/auth/lockouts",
+            get(routes::lockouts::list)
+                .route_layer(require(Role::Admin, Scope::Admin))
+                .merge(
+                    delete(routes::lockouts::clear)
+                        .route_layer(require(Role::Admin, Scope::Admin))
+                        .route_layer(audit("lockouts.clear")),
+                ),
+        )
+        .route(
+            "/auth/keys",
+            get(routes::keys::list)
+                .route_layer(require(Role::Admin, Scope::Admin))
+                .merge(
+                    post(routes::keys::create)
+                        .route_layer(require(Role::Admin, Scope::Admin))
+                        .route_layer(audit("keys.create")),
+                ),
+        )
+        .route(
+            "/auth/keys/:id",
+            delete(routes::keys::revoke)
+                .route_layer(require(Role::Admin, Scope::Admin))
+                .route_layer(audit("keys.revoke")),
+        )
+        .route("/auth/logout", post(routes::auth::logout))
+        .route(
+            "/auth/totp",
+            post(routes::totp::enroll)
+                .route_layer(audit("totp.enroll"))
+                .merge(delete(routes::totp::disable).route_layer(audit("totp.disable"))),
+        )
+        .route(
+            "/auth/totp/confirm",
+            post(routes::totp::confirm).route_layer(audit("totp.confirm")),
+        )
+        .route(
+            "/auth/totp/:user",
+            delete(routes::totp::reset)
+                .route_layer(require(Role::Admin, Scope::Admin))
+                .route_layer(audit("totp.reset")),
+        )
+        .route("/auth/refresh", post(routes::auth::refresh))
+        .route(
+            "/server/command",
+            post(routes::server::command::execute)
+                .route_layer(require(routes::server::command::ROLE, Scope::ConsoleWrite))
+                .route_layer(audit("server.command")),
+        )
+        .route(
+            "/server/config",
+            get(routes::server::config::execute)
+                .route_layer(require(Role::Viewer, Scope::ServerRead))
+                .merge(
+                    patch(routes::server::config::update)
+                        .route_layer(require(Role::Admin, Scope::ServerConfig))
+                        .route_layer(audit("server.config")),
+                ),
+        )
+        .route(
+            "/server/console",
+            get(routes::server::console::execute)
+                .route_layer(require(Role::Viewer, Scope::ConsoleRead)),
+        )
+        .route(
+            "/server/changes",
+            get(routes::server::changes::execute)
+                .route_layer(require(Role::Viewer, Scope::ServerRead)),
+        )
+        .route(
+            "/server/crash-reports",
+            get(routes::server::crash_reports::execute)
+                .route_layer(require(Role::Viewer, Scope::ServerRead)),
+        )
+        .route(
+            "/server/crash-reports/:name",
+            get(routes::server::crash_reports::read)
+                .route_layer(require(Role::Viewer, Scope::ServerRead)),
+        )
+        .route(
+            "/server/logs/debug",
+            get(routes::server::logs::debug).route_layer(require(Role::Viewer, Scope::ServerRead)),
+        )
+        .route(
+            "/server/mods",
+            get(routes::server::mods::execute).route_layer(require(Role::Viewer, Scope::ModsRead)),
+        )
+        .route(
+            "/server/mods/upload",
+            post(routes::server::mods::upload)
+                .route_layer(require(Role::Admin, Scope::ModsWrite))
+                .route_layer(audit("mods.upload")),
+        )
+        .route(
+            "/server/players",
+            get(routes::server::players::execute)
+                .route_layer(require(Role::Viewer, Scope::PlayersRead)),
+        )
+        .route(
+            "/server/rcon",
+            post(routes::server::rcon::provision)
+                .route_layer(require(Role::Admin, Scope::ServerConfig))
+                .route_layer(audit("server.rcon")),
+        )
+        .route(
+            "/server/restart",
+            post(routes::server::restart::execute)
+                .route_layer(require(Role::Operator, Scope::ServerRestart))
+                .route_layer(audit("server.restart")),
+        )
+        .route(
+            "/server/start",
+            post(routes::server::power::start)
+                .route_layer(require(Role::Operator, Scope::ServerPower))
+                .route_layer(audit("server.start")),
+        )
+        .route(
+            "/server/stop",
+            post(routes::server::power::stop)
+                .route_layer(require(Role::Operator, Scope::ServerPower))
+                .route_layer(audit("server.stop")),
+        )
+        .route(
+            "/server/kill",
+            post(routes::server::power::kill)
+                .route_layer(require(Role::Admin, Scope::ServerPower))
+                .route_layer(audit("server.kill")),
+        )
+        .route(
+            "/server/status",
+            get(routes::server::power::status)
+                .route_layer(require(Role::Viewer, Scope::ServerRead)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            routes::access::authenticate,
+        ));
+
+    let app = Router::new()
+        .merge(public)
+        .merge(protected)
         .layer(DefaultBodyLimit::disable())
         .layer(
             CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
+                .allow_origin(AllowOrigin::predicate({
+                    let state = state.clone();
+
+                    move |origin: &HeaderValue, _| {
+                        origin.to_str().is_ok_and(|origin| {
+                            state
+                                .cors
+                                .read()
+                                .expect("cors lock poisoned")
+                                .allows(origin)
+                        })
+                    }
+                }))
+                .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+                .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+                .allow_credentials(config.cors.credentials),
         )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            routes::access::filter,
+        ))
         .with_state(state);
 
-    // run our app with hyper, listening globally on port 3000
-    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let addr = SocketAddr::new(config.bind, config.port);
 
-    let mut config = None;
+    let mut tls = None;
 
-    if args.ssl {
-        if args.ssl_key.is_none() || args.ssl_cert.is_none() {
-            panic!("IMAGINE A GOOD WEB SERVER WITH SSL AND WITHOUT A KEY OR CERTIFICATE");
-        }
-
-        config = Some(
-            RustlsConfig::from_pem_file(
-                PathBuf::from(args.ssl_cert.unwrap()),
-                PathBuf::from(args.ssl_key.unwrap()),
-            )
+    if config.tls.enabled {
+        let (cert, key) = match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+            (None, None) => tls::self_signed(&data_dir, &tls::names(config.bind)).await?,
+            (Some(cert), None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "TLS certificate {} has no key, set tls.key or --ssl-key",
+                        cert.display()
+                    ),
+                ))
+            }
+            (None, Some(key)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "TLS key {} has no certificate, set tls.cert or --ssl-cert",
+                        key.display()
+                    ),
+                ))
+            }
+        };
+        let rustls = RustlsConfig::from_pem_file(&cert, &key)
             .await
-            .unwrap(),
-        );
+            .map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!(
+                        "failed to load TLS certificate {} and key {}: {error}",
+                        cert.display(),
+                        key.display()
+                    ),
+                )
+            })?;
+
+        tokio::spawn(tls::watch(rustls.clone(), cert, key));
+
+        tls = Some(rustls);
     }
 
-    println!("Running on 0.0.0.0:{}", args.port);
+    println!("Running on {addr}");
 
-    if let Some(config) = config {
-        axum_server::bind_rustls(addr, config)
-            .serve(app.into_make_service())
+    if let Some(tls) = tls {
+        axum_server::bind_rustls(addr, tls)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .unwrap();
     } else {
         axum_server::bind(addr)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .unwrap();
     }
 
     Ok(())
 }
+
+fn loopback(bind: IpAddr) -> IpAddr {
+    match bind {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+    }
+}
+
+fn hash_password() -> Result<(), std::io::Error> {
+    let mut password = String::new();
+
+    eprint!("Password: ");
+    io::stdin().read_line(&mut password)?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    if password.is_empty() {
+        panic!("Password must not be empty!")
+    }
+
+    println!("{}", utils::hash_password(password));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(["server", "/srv/minecraft"].iter().chain(flags))
+    }
+
+    fn config(data: &str) -> Config {
+        toml::from_str(data).expect("valid config")
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let args = args(&[
+            "--port",
+            "4000",
+            "--no-crash-restart",
+            "--stop-timeout",
+            "5",
+        ]);
+        let mut config = config("port = 5000\nstart_command = \"java -jar server.jar\"\n[limits]\nstop_timeout = 30\nrcon_timeout = 3");
+
+        args.apply(&mut config);
+
+        assert_eq!(config.port, 4000);
+        assert!(!config.limits.crash_restart);
+        assert_eq!(config.limits.stop_timeout, 5);
+        // Anything without a flag is left to the file.
+        assert_eq!(config.start_command, "java -jar server.jar");
+        assert_eq!(config.limits.rcon_timeout, 3);
+    }
+
+    #[test]
+    fn flags_are_applied_again_on_reload() {
+        let args = args(&[
+            "--port",
+            "4000",
+            "--ssl",
+            "--ssl-cert",
+            "cert.pem",
+            "--ssl-key",
+            "key.pem",
+        ]);
+        let mut loaded = config("port = 5000");
+
+        args.apply(&mut loaded);
+
+        // The file changed the port and tls, and something else.
+        let mut reloaded =
+            config("port = 6000\nstart_command = \"sh start.sh\"\n[tls]\nenabled = false");
+
+        args.apply(&mut reloaded);
+
+        assert_eq!(reloaded.port, 4000);
+        assert!(reloaded.tls.enabled);
+        assert_eq!(reloaded.tls.cert, Some(PathBuf::from("cert.pem")));
+        assert_eq!(reloaded.tls.key, Some(PathBuf::from("key.pem")));
+        assert_eq!(reloaded.start_command, "sh start.sh");
+        assert!(loaded.tls == reloaded.tls);
+    }
+
+    #[test]
+    fn insecure_mode_binds_to_loopback() {
+        assert!(args(&["--insecure-no-auth"]).insecure_no_auth);
+        assert!(!args(&[]).insecure_no_auth);
+        assert_eq!(
+            loopback("0.0.0.0".parse().unwrap()),
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        );
+        assert_eq!(
+            loopback("::".parse().unwrap()),
+            IpAddr::V6(Ipv6Addr::LOCALHOST)
+        );
+    }
+
+    #[test]
+    fn no_flags_leave_the_file_alone() {
+        let mut config = config("port = 5000\n[limits]\ncrash_restart = false");
+
+        args(&[]).apply(&mut config);
+
+        assert!(config == self::config("port = 5000\n[limits]\ncrash_restart = false"));
+    }
+}
@@ -0,0 +1,186 @@
+use lazy_regex::{lazy_regex, Lazy};
+use regex::Regex;
+
+static FRAME_REX: Lazy<Regex> = lazy_regex!(
+    r"^\s*at (?:[^\s/(]*/)*?(?:([\w.-]+)(?:@[^\s/]*)?/)?([\w$.]+)\.([\w$<>]+)\([^)]*\)(?:\s*~?\[([^\]]*)\])?"
+);
+static JAR_REX: Lazy<Regex> = lazy_regex!(r"([^\[\]/\\%:!]+\.jar)");
+static MIXIN_REX: Lazy<Regex> = lazy_regex!(r"([\w.-]*mixins?[\w.-]*\.json)");
+static MOD_ID_REX: Lazy<Regex> = lazy_regex!(r"\(([a-z][a-z0-9_]{1,63})\), Version");
+
+pub(crate) struct Frame {
+    pub(crate) module: Option<String>,
+    pub(crate) class: String,
+    pub(crate) method: String,
+    pub(crate) jar: Option<String>,
+}
+
+impl Frame {
+    pub(crate) fn package(&self) -> Option<&str> {
+        self.class.rsplit_once('.').map(|(package, _)| package)
+    }
+}
+
+pub(crate) struct Report {
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) mixins: Vec<String>,
+    pub(crate) suspected: Vec<String>,
+}
+
+pub(crate) fn parse<T: AsRef<str>>(data: T) -> Report {
+    let mut frames = vec![];
+    let mut mixins = vec![];
+    let mut suspected = vec![];
+    let mut in_suspects = false;
+
+    for line in data.as_ref().lines() {
+        let line = line.trim_end();
+
+        if let Some(captures) = FRAME_REX.captures(line) {
+            frames.push(Frame {
+                module: captures.get(1).map(|module| module.as_str().to_string()),
+                class: captures[2].to_string(),
+                method: captures[3].to_string(),
+                jar: captures
+                    .get(4)
+                    .and_then(|source| JAR_REX.captures(source.as_str()))
+                    .map(|jar| jar[1].to_string()),
+            });
+        }
+
+        for captures in MIXIN_REX.captures_iter(line) {
+            let mixin = captures[1].to_string();
+
+            if !mixins.contains(&mixin) {
+                mixins.push(mixin);
+            }
+        }
+
+        // Forge lists the mods it blames under `Suspected Mod(s):`, one per
+        // line, until the next blank line.
+        if line.contains("Suspected Mod") {
+            in_suspects = true;
+        } else if line.trim().is_empty() {
+            in_suspects = false;
+        }
+
+        if in_suspects {
+            for captures in MOD_ID_REX.captures_iter(line) {
+                let mod_id = captures[1].to_string();
+
+                if !suspected.contains(&mod_id) {
+                    suspected.push(mod_id);
+                }
+            }
+        }
+    }
+
+    Report {
+        frames,
+        mixins,
+        suspected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORGE_1_20_1: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/crash-reports/forge-1.20.1.txt"
+    ));
+    const FORGE_1_12_2: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/crash-reports/forge-1.12.2.txt"
+    ));
+
+    #[test]
+    fn parses_modern_frames() {
+        let report = parse(FORGE_1_20_1);
+        let first = &report.frames[0];
+
+        assert_eq!(first.module, None);
+        assert_eq!(first.class, "com.example.goblins.entity.ai.ChaseGoal");
+        assert_eq!(first.method, "m_8037_");
+        assert_eq!(first.jar.as_deref(), Some("goblins-1.4.2.jar"));
+        assert_eq!(first.package(), Some("com.example.goblins.entity.ai"));
+
+        let vanilla = &report.frames[1];
+
+        assert_eq!(
+            vanilla.jar.as_deref(),
+            Some("server-1.20.1-20230612.114412-srg.jar")
+        );
+
+        let lambda = report
+            .frames
+            .iter()
+            .find(|frame| frame.method.starts_with("lambda$"))
+            .expect("lambda frame");
+
+        assert_eq!(lambda.method, "lambda$tick$6");
+    }
+
+    #[test]
+    fn parses_frame_modules() {
+        let report = parse(FORGE_1_20_1);
+        let transformed = report
+            .frames
+            .iter()
+            .find(|frame| frame.module.as_deref() == Some("goblins"))
+            .expect("goblins frame");
+
+        assert_eq!(transformed.class, "com.example.goblins.entity.ai.ChaseGoal");
+
+        let thread = report
+            .frames
+            .iter()
+            .find(|frame| frame.module.as_deref() == Some("java.base"))
+            .expect("java.base frame");
+
+        assert_eq!(thread.class, "java.lang.Thread");
+        assert_eq!(thread.method, "run");
+        assert_eq!(thread.jar, None);
+    }
+
+    #[test]
+    fn parses_mixins_and_suspects() {
+        let report = parse(FORGE_1_20_1);
+
+        assert_eq!(
+            report.mixins,
+            vec!["goblins.mixins.json", "curios.mixins.json"]
+        );
+        assert_eq!(report.suspected, vec!["goblins"]);
+    }
+
+    #[test]
+    fn parses_legacy_report() {
+        let report = parse(FORGE_1_12_2);
+        let classes: Vec<&str> = report
+            .frames
+            .iter()
+            .map(|frame| frame.class.as_str())
+            .collect();
+
+        assert_eq!(classes.len(), 11);
+        assert_eq!(classes[0], "com.example.pipes.tile.TilePipe");
+        assert_eq!(classes[9], "com.example.corelib.graph.Graph");
+        assert!(report
+            .frames
+            .iter()
+            .all(|frame| frame.module.is_none() && frame.jar.is_none()));
+        assert!(report.mixins.is_empty());
+        assert!(report.suspected.is_empty());
+    }
+
+    #[test]
+    fn suspects_end_at_blank_line() {
+        let report = parse(
+            "Suspected Mods:\n\tFoo (foo), Version: 1\n\tBar (bar), Version: 2\n\nLater (later), Version: 3\n",
+        );
+
+        assert_eq!(report.suspected, vec!["foo", "bar"]);
+    }
+}
@@ -0,0 +1,85 @@
+// Minecraft formats console output with `§` followed by a color or style code.
+
+pub(crate) fn strip<T: AsRef<str>>(text: T) -> String {
+    let mut output = String::new();
+    let mut chars = text.as_ref().chars();
+
+    while let Some(char) = chars.next() {
+        if char == '§' {
+            chars.next();
+        } else {
+            output.push(char);
+        }
+    }
+
+    output
+}
+
+pub(crate) fn to_html<T: AsRef<str>>(text: T) -> String {
+    let mut output = String::new();
+    let mut open = 0;
+    let mut chars = text.as_ref().chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '§' => {
+                let Some(code) = chars.next().map(|code| code.to_ascii_lowercase()) else {
+                    break;
+                };
+
+                if let Some(color) = color(code) {
+                    output.push_str(&"</span>".repeat(open));
+                    output.push_str(&format!("<span style=\"color:{color}\">"));
+                    open = 1;
+                } else if let Some(style) = style(code) {
+                    output.push_str(&format!("<span style=\"{style}\">"));
+                    open += 1;
+                } else if code == 'r' {
+                    output.push_str(&"</span>".repeat(open));
+                    open = 0;
+                }
+            }
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            char => output.push(char),
+        }
+    }
+
+    output.push_str(&"</span>".repeat(open));
+
+    output
+}
+
+fn color(code: char) -> Option<&'static str> {
+    Some(match code {
+        '0' => "#000000",
+        '1' => "#0000aa",
+        '2' => "#00aa00",
+        '3' => "#00aaaa",
+        '4' => "#aa0000",
+        '5' => "#aa00aa",
+        '6' => "#ffaa00",
+        '7' => "#aaaaaa",
+        '8' => "#555555",
+        '9' => "#5555ff",
+        'a' => "#55ff55",
+        'b' => "#55ffff",
+        'c' => "#ff5555",
+        'd' => "#ff55ff",
+        'e' => "#ffff55",
+        'f' => "#ffffff",
+        _ => return None,
+    })
+}
+
+fn style(code: char) -> Option<&'static str> {
+    Some(match code {
+        'l' => "font-weight:bold",
+        'm' => "text-decoration:line-through",
+        'n' => "text-decoration:underline",
+        'o' => "font-style:italic",
+        _ => return None,
+    })
+}
@@ -1,2 +1,4 @@
+pub(crate) mod crash_report;
+pub(crate) mod formatting;
 pub(crate) mod manifest;
 pub(crate) mod properties;
@@ -1,67 +1,580 @@
-use std::collections::HashMap;
+use std::fmt;
 
-pub(crate) enum Value {
-    Integer(u32),
-    String(String),
-    Boolean(bool),
-    None,
+// What java.util.Properties counts as whitespace.
+const WHITESPACE: [char; 3] = [' ', '\t', '\x0c'];
+
+struct Line {
+    // Exactly as read, continuations included.
+    raw: String,
+    // `\n`, `\r\n`, `\r`, or nothing on a last line without one.
+    terminator: String,
+    // Comments and blank lines have no key.
+    entry: Option<(String, String)>,
+}
+
+// A .properties file kept line by line, so it can be written back with the
+// comments, the key order and every untouched line exactly as they were.
+// Follows the java.util.Properties format the server reads it with.
+pub(crate) struct Document {
+    lines: Vec<Line>,
+    // For lines that are added.
+    newline: &'static str,
 }
 
-impl Value {
-    pub(crate) fn as_u32(&self) -> Option<u32> {
-        match self {
-            Value::Integer(integer) => Some(*integer),
-            _ => None,
+impl Document {
+    pub(crate) fn parse<T: Into<String>>(data: T) -> Document {
+        let data: String = data.into();
+        let physical = physical_lines(&data);
+        let newline = match physical.iter().map(|(_, terminator)| *terminator).next() {
+            Some("\r\n") => "\r\n",
+            Some("\r") => "\r",
+            _ => "\n",
+        };
+
+        let mut lines = vec![];
+        let mut physical = physical.into_iter();
+
+        while let Some((line, mut terminator)) = physical.next() {
+            let trimmed = line.trim_start_matches(WHITESPACE);
+
+            if trimmed.is_empty() || trimmed.starts_with(['#', '!']) {
+                lines.push(Line {
+                    raw: line.to_string(),
+                    terminator: terminator.to_string(),
+                    entry: None,
+                });
+
+                continue;
+            }
+
+            let mut logical = trimmed.to_string();
+            let mut raw = line.to_string();
+
+            while continues(&logical) {
+                logical.pop();
+
+                let Some((next, next_terminator)) = physical.next() else {
+                    break;
+                };
+
+                raw.push_str(terminator);
+                raw.push_str(next);
+                logical.push_str(next.trim_start_matches(WHITESPACE));
+                terminator = next_terminator;
+            }
+
+            lines.push(Line {
+                raw,
+                terminator: terminator.to_string(),
+                entry: Some(split(&logical)),
+            });
         }
+
+        Self { lines, newline }
+    }
+
+    // Later entries win, as they do for the server.
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|(name, _)| *name == key)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| {
+            line.entry
+                .as_ref()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+        })
     }
 
-    pub(crate) fn as_string(&self) -> Option<String> {
-        match self {
-            Value::String(string) => Some(string.clone()),
-            _ => None,
+    // Rewrites the line of the key in place, or appends it when missing.
+    pub(crate) fn set<T: Into<String>>(&mut self, key: &str, value: T) {
+        let value: String = value.into();
+        let raw = format!("{}={}", escape(key, true), escape(&value, false));
+        let line = self
+            .lines
+            .iter_mut()
+            .rev()
+            .find(|line| line.entry.as_ref().is_some_and(|(name, _)| name == key));
+
+        if let Some(line) = line {
+            line.raw = raw;
+            line.entry = Some((key.to_string(), value));
+
+            return;
+        }
+
+        // The new line takes over whether the file ends with a line break.
+        let mut terminator = self.newline.to_string();
+
+        if let Some(last) = self.lines.last_mut() {
+            if last.terminator.is_empty() {
+                last.terminator = self.newline.to_string();
+                terminator = String::new();
+            }
+
+            // A continuation left open at the end would swallow the new line,
+            // a blank one ends it without touching the line itself.
+            if last.entry.is_some() && continues(&last.raw) {
+                self.lines.push(Line {
+                    raw: String::new(),
+                    terminator: self.newline.to_string(),
+                    entry: None,
+                });
+            }
         }
+
+        self.lines.push(Line {
+            raw,
+            terminator,
+            entry: Some((key.to_string(), value)),
+        });
     }
+}
 
-    pub(crate) fn as_bool(&self) -> Option<bool> {
-        match self {
-            Value::Boolean(boolean) => Some(*boolean),
-            _ => None,
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}{}", line.raw, line.terminator)?;
         }
+
+        Ok(())
     }
 }
 
-pub(crate) fn parse<T: Into<String>>(data: T) -> HashMap<String, Value> {
-    let data: String = data.into();
-    let mut table = HashMap::new();
+// Lines end with `\n`, `\r\n` or a lone `\r`, the terminator is kept so
+// every line can be written back as it was.
+fn physical_lines(data: &str) -> Vec<(&str, &str)> {
+    let mut lines = vec![];
+    let mut rest = data;
 
-    for line in data.split('\n') {
-        if line.starts_with('#') {
-            continue;
-        }
+    while !rest.is_empty() {
+        let Some(end) = rest.find(['\r', '\n']) else {
+            lines.push((rest, ""));
 
-        let arr: Vec<&str> = line.split('=').collect();
+            break;
+        };
 
-        let key = arr[0].to_string();
-        let value = if arr.len() > 1 {
-            Some(arr[1].to_string())
+        let length = if rest[end..].starts_with("\r\n") {
+            2
         } else {
-            None
+            1
         };
 
-        if let Some(value) = value {
-            if value.trim() == "" {
-                table.insert(key, Value::None);
-            } else if let Ok(integer) = value.parse() {
-                table.insert(key, Value::Integer(integer));
-            } else if let Ok(boolean) = value.parse() {
-                table.insert(key, Value::Boolean(boolean));
-            } else {
-                table.insert(key, Value::String(value));
+        lines.push((&rest[..end], &rest[end..end + length]));
+        rest = &rest[end + length..];
+    }
+
+    lines
+}
+
+// An odd number of trailing backslashes, the last one is not escaped.
+fn continues(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+// The key ends at the first unescaped `=`, `:` or whitespace, which may be
+// followed by more whitespace and one `=` or `:`.
+fn split(line: &str) -> (String, String) {
+    let mut escaped = false;
+    let mut end = line.len();
+
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || WHITESPACE.contains(&c) {
+            end = index;
+
+            break;
+        }
+    }
+
+    let mut value = line[end..].trim_start_matches(WHITESPACE);
+
+    if let Some(rest) = value.strip_prefix(['=', ':']) {
+        value = rest.trim_start_matches(WHITESPACE);
+    }
+
+    (unescape(&line[..end]), unescape(value))
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    // Consecutive \uXXXX escapes, kept together so surrogate pairs decode.
+    let mut units = vec![];
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            flush(&mut units, &mut result);
+            result.push(c);
+
+            continue;
+        }
+
+        let Some(escaped) = chars.next() else {
+            break;
+        };
+
+        if escaped == 'u' {
+            let hex: String = chars.clone().take(4).collect();
+
+            if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                units.push(u16::from_str_radix(&hex, 16).expect("checked to be hex"));
+                chars.nth(3);
+
+                continue;
             }
-        } else {
-            table.insert(key, Value::None);
+
+            // Java rejects these, they are kept as written instead.
+            flush(&mut units, &mut result);
+            result.push_str("\\u");
+
+            continue;
         }
+
+        flush(&mut units, &mut result);
+        result.push(match escaped {
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            'f' => '\x0c',
+            other => other,
+        });
     }
 
-    table
+    flush(&mut units, &mut result);
+
+    result
+}
+
+fn flush(units: &mut Vec<u16>, result: &mut String) {
+    result.extend(
+        char::decode_utf16(units.drain(..)).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+    );
+}
+
+// Escapes like Properties.store, apart from leaving non-ASCII text readable,
+// the server reads the file as UTF-8.
+fn escape(text: &str, key: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\x0c' => result.push_str("\\f"),
+            '=' | ':' | '#' | '!' => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' ' if key || index == 0 => result.push_str("\\ "),
+            c if c.is_control() => result.push_str(&format!("\\u{:04X}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VANILLA: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/vanilla-1.21.properties"
+    ));
+    const FORGE: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/forge-1.12.properties"
+    ));
+    const EDGE_CASES: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/edge-cases.properties"
+    ));
+
+    // Every fixture with its line breaks as written, and turned into each of
+    // the other kinds.
+    fn variants() -> Vec<String> {
+        [VANILLA, FORGE, EDGE_CASES]
+            .iter()
+            .flat_map(|data| {
+                let lf = data.replace("\r\n", "\n");
+
+                [
+                    data.to_string(),
+                    lf.replace('\n', "\r\n"),
+                    lf.replace('\n', "\r"),
+                    lf,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn untouched_files_are_written_back_byte_for_byte() {
+        for data in variants() {
+            assert_eq!(Document::parse(data.clone()).to_string(), data);
+        }
+    }
+
+    #[test]
+    fn line_breaks_do_not_change_the_entries() {
+        let expected: Vec<(String, String)> = Document::parse(EDGE_CASES)
+            .entries()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        for data in [
+            EDGE_CASES.replace('\n', "\r\n"),
+            EDGE_CASES.replace('\n', "\r"),
+        ] {
+            let document = Document::parse(data);
+            let entries: Vec<(String, String)> = document
+                .entries()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+
+            assert_eq!(entries, expected);
+        }
+    }
+
+    #[test]
+    fn separators() {
+        let document = Document::parse(EDGE_CASES);
+
+        assert_eq!(document.get("colon"), Some("separated"));
+        assert_eq!(document.get("whitespace"), Some("separated value"));
+        assert_eq!(document.get("spaced-equals"), Some("equals"));
+        assert_eq!(document.get("spaced-colon"), Some("colon"));
+        assert_eq!(document.get("indented"), Some("with a tab"));
+        assert_eq!(document.get("empty"), Some(""));
+        assert_eq!(document.get("last"), Some("one"));
+    }
+
+    #[test]
+    fn comments_are_not_entries() {
+        let document = Document::parse(EDGE_CASES);
+
+        assert!(document
+            .entries()
+            .all(|(key, _)| !key.starts_with(['#', '!'])));
+        assert_eq!(document.get("!"), None);
+        assert_eq!(Document::parse(VANILLA).entries().count(), 61);
+    }
+
+    #[test]
+    fn escapes() {
+        let document = Document::parse(EDGE_CASES);
+
+        assert_eq!(document.get("emoji"), Some("😀 café"));
+        assert_eq!(document.get("escaped key:name"), Some("value"));
+        assert_eq!(document.get("trailing"), Some("ends with a backslash\\"));
+        assert_eq!(document.get("broken"), Some("\\uZZ12"));
+        assert_eq!(
+            Document::parse(FORGE).get("motd"),
+            Some("§aModded §r= Survival")
+        );
+        assert_eq!(
+            Document::parse(VANILLA).get("level-type"),
+            Some("minecraft:normal")
+        );
+    }
+
+    #[test]
+    fn continuations() {
+        let document = Document::parse(EDGE_CASES);
+
+        assert_eq!(document.get("long"), Some("first second third"));
+        // The line after a continued one is an entry of its own again.
+        assert_eq!(document.get("escaped key:name"), Some("value"));
+    }
+
+    #[test]
+    fn continuation_at_the_end_is_kept() {
+        let data = "a=1\nb=2\\";
+        let document = Document::parse(data);
+
+        assert_eq!(document.get("b"), Some("2"));
+        assert_eq!(document.to_string(), data);
+    }
+
+    #[test]
+    fn values_containing_separators() {
+        assert_eq!(
+            Document::parse(EDGE_CASES).get("motd"),
+            Some("Welcome=home=friend")
+        );
+        assert_eq!(Document::parse("motd=a:b c").get("motd"), Some("a:b c"));
+    }
+
+    #[test]
+    fn seeds_are_kept_as_written() {
+        assert_eq!(
+            Document::parse(FORGE).get("level-seed"),
+            Some("-4172144997902289642")
+        );
+        assert_eq!(
+            Document::parse(EDGE_CASES).get("level-seed"),
+            Some("9223372036854775807")
+        );
+        assert_eq!(Document::parse(VANILLA).get("level-seed"), Some(""));
+    }
+
+    #[test]
+    fn later_entries_win() {
+        assert_eq!(Document::parse("a=1\na=2\n").get("a"), Some("2"));
+    }
+
+    #[test]
+    fn physical_lines_keep_their_terminators() {
+        assert_eq!(
+            physical_lines("a\r\nb\rc\nd"),
+            vec![("a", "\r\n"), ("b", "\r"), ("c", "\n"), ("d", "")]
+        );
+        assert_eq!(physical_lines("a\n\n"), vec![("a", "\n"), ("", "\n")]);
+    }
+
+    #[test]
+    fn odd_backslashes_continue() {
+        assert!(continues("a\\"));
+        assert!(!continues("a\\\\"));
+        assert!(continues("a\\\\\\"));
+        assert!(!continues("a"));
+    }
+
+    #[test]
+    fn set_rewrites_only_its_own_line() {
+        for data in variants() {
+            let mut document = Document::parse(data.clone());
+            let key = document
+                .entries()
+                .map(|(key, _)| key.to_string())
+                .find(|key| key == "motd" || key == "difficulty")
+                .expect("every fixture has a motd or difficulty");
+
+            document.set(&key, "changed");
+
+            let written = document.to_string();
+            let before = physical_lines(&data);
+            let after = physical_lines(&written);
+
+            assert_eq!(before.len(), after.len());
+            assert_eq!(Document::parse(written.clone()).get(&key), Some("changed"));
+
+            let differing: Vec<_> = before
+                .iter()
+                .zip(&after)
+                .filter(|(before, after)| before != after)
+                .collect();
+
+            assert_eq!(differing.len(), 1, "{written:?}");
+            // The rewritten line keeps the line break it had.
+            assert_eq!(differing[0].0 .1, differing[0].1 .1);
+        }
+    }
+
+    #[test]
+    fn set_rewrites_the_entry_that_wins() {
+        let mut document = Document::parse("a=1\r\na=2\r\nb=3\r\n");
+
+        document.set("a", "4");
+
+        assert_eq!(document.to_string(), "a=1\r\na=4\r\nb=3\r\n");
+    }
+
+    #[test]
+    fn set_appends_with_the_files_line_breaks() {
+        let mut document = Document::parse(FORGE);
+
+        document.set("accepts-transfers", "true");
+
+        assert_eq!(
+            document.to_string(),
+            format!("{FORGE}accepts-transfers=true\r\n")
+        );
+    }
+
+    #[test]
+    fn set_keeps_a_missing_final_line_break_missing() {
+        let mut document = Document::parse("a=1\r\nb=2");
+
+        document.set("c", "3");
+
+        assert_eq!(document.to_string(), "a=1\r\nb=2\r\nc=3");
+
+        let mut document = Document::parse(EDGE_CASES);
+
+        document.set("added", "yes");
+
+        let written = document.to_string();
+
+        assert!(written.starts_with(EDGE_CASES));
+        assert!(written.ends_with("\nadded=yes"));
+    }
+
+    #[test]
+    fn set_on_an_empty_file() {
+        let mut document = Document::parse("");
+
+        document.set("a", "1");
+
+        assert_eq!(document.to_string(), "a=1\n");
+    }
+
+    #[test]
+    fn set_does_not_join_a_dangling_continuation() {
+        let mut document = Document::parse("a=1\nb=2\\\n");
+
+        document.set("c", "3");
+
+        let written = document.to_string();
+
+        assert_eq!(written, "a=1\nb=2\\\n\nc=3\n");
+        assert_eq!(Document::parse(written).get("c"), Some("3"));
+    }
+
+    #[test]
+    fn set_round_trips_values_that_need_escaping() {
+        let values = [
+            "",
+            " leading space",
+            "trailing space ",
+            "line\nbreak\r\nand\rmore",
+            "a=b:c#d!e",
+            "back\\slash\\",
+            "tab\tand\u{c}feed",
+            "\u{a7}aColored \u{a7}r\u{1f600} caf\u{e9}",
+            "\\u0041 not an escape",
+        ];
+
+        for data in variants() {
+            let mut document = Document::parse(data);
+
+            for (index, value) in values.iter().enumerate() {
+                document.set(&format!("key-{index}"), *value);
+            }
+
+            document.set("spaced key:with=separators", "value");
+
+            let parsed = Document::parse(document.to_string());
+
+            for (index, value) in values.iter().enumerate() {
+                assert_eq!(parsed.get(&format!("key-{index}")), Some(*value));
+            }
+
+            assert_eq!(parsed.get("spaced key:with=separators"), Some("value"));
+            assert_eq!(
+                parsed.entries().collect::<Vec<_>>(),
+                document.entries().collect::<Vec<_>>()
+            );
+        }
+    }
 }
@@ -0,0 +1,571 @@
+use std::{
+    fmt, future, io,
+    sync::{Arc, RwLock},
+    task::Poll,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, ReadBuf},
+    net::TcpStream,
+    sync::{watch, Mutex},
+    time,
+};
+
+use crate::{data::app, supervisor::Phase};
+
+const PACKET_RESPONSE: i32 = 0;
+const PACKET_COMMAND: i32 = 2;
+const PACKET_AUTH: i32 = 3;
+
+// Minecraft never sends more than 4096 bytes of body in a single packet, the
+// limit only guards against reading garbage from something that is not rcon.
+const MAX_PACKET_SIZE: i32 = 4096 + 10;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Io(io::Error),
+    // The command was sent but the connection dropped before the answer.
+    Unanswered(io::Error),
+    Timeout,
+    Unauthorized,
+    Protocol(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "rcon connection failed: {error}"),
+            Error::Unanswered(error) => write!(
+                f,
+                "rcon connection failed after sending the command, it may have run: {error}"
+            ),
+            Error::Timeout => write!(f, "rcon request timed out"),
+            Error::Unauthorized => write!(f, "rcon password was rejected"),
+            Error::Protocol(message) => write!(f, "invalid rcon response: {message}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Health {
+    Connected,
+    Disconnected,
+    Unauthorized,
+}
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+// Where a request failed, only one that never reached the server is safe to
+// send again.
+enum Failure {
+    Unsent(Error),
+    // Along with whatever part of the answer came back before.
+    Unanswered(Error, Option<Vec<u8>>),
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::Unsent(error) => error,
+            Failure::Unanswered(Error::Io(error), _) => Error::Unanswered(error),
+            Failure::Unanswered(error, _) => error,
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl Connection {
+    async fn open(address: &str, password: &str) -> Result<Connection, Error> {
+        let stream = TcpStream::connect(address).await?;
+        let mut connection = Connection { stream, next_id: 1 };
+        let id = connection.next_id();
+
+        connection.write(id, PACKET_AUTH, password).await?;
+
+        // Some servers send an empty response value before the auth response.
+        loop {
+            let packet = connection.read().await?;
+
+            if packet.id == -1 {
+                return Err(Error::Unauthorized);
+            }
+
+            if packet.kind == PACKET_COMMAND {
+                return Ok(connection);
+            }
+        }
+    }
+
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+
+        self.next_id = if id == i32::MAX { 1 } else { id + 1 };
+
+        id
+    }
+
+    // Responses longer than 4096 bytes are split over several packets without
+    // any end marker. Sending a second request right after the command and
+    // collecting everything until its answer arrives reassembles them. The
+    // fragments are decoded together, a character may be split between them.
+    async fn execute(&mut self, command: &str) -> Result<String, Failure> {
+        let id = self.next_id();
+        let marker = self.next_id();
+        let mut body: Option<Vec<u8>> = None;
+
+        if self.is_closed().await {
+            return Err(Failure::Unsent(Error::Io(
+                io::ErrorKind::ConnectionReset.into(),
+            )));
+        }
+
+        self.write(id, PACKET_COMMAND, command)
+            .await
+            .map_err(Failure::Unsent)?;
+        self.write(marker, PACKET_RESPONSE, "")
+            .await
+            .map_err(|error| Failure::Unanswered(error, None))?;
+
+        loop {
+            match self.read().await {
+                Ok(packet) if packet.id == id => {
+                    body.get_or_insert_with(Vec::new).extend(packet.body);
+                }
+                Ok(packet) if packet.id == marker => {
+                    return Ok(String::from_utf8_lossy(&body.unwrap_or_default()).into_owned())
+                }
+                // Leftovers from an earlier request that timed out.
+                Ok(_) => continue,
+                Err(error) => return Err(Failure::Unanswered(error, body)),
+            }
+        }
+    }
+
+    // A connection the server closed in the meantime, like before a restart,
+    // still takes writes. Only reading from it tells.
+    async fn is_closed(&self) -> bool {
+        let mut buffer = [0; 1];
+        let mut buffer = ReadBuf::new(&mut buffer);
+
+        future::poll_fn(|context| {
+            Poll::Ready(matches!(
+                self.stream.poll_peek(context, &mut buffer),
+                Poll::Ready(Ok(0) | Err(_))
+            ))
+        })
+        .await
+    }
+
+    async fn write(&mut self, id: i32, kind: i32, body: &str) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(body.len() + 14);
+
+        data.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(body.as_bytes());
+        data.extend_from_slice(&[0, 0]);
+
+        self.stream.write_all(&data).await?;
+
+        Ok(())
+    }
+
+    async fn read(&mut self) -> Result<Packet, Error> {
+        let length = self.stream.read_i32_le().await?;
+
+        if !(10..=MAX_PACKET_SIZE).contains(&length) {
+            return Err(Error::Protocol("packet length out of range"));
+        }
+
+        let id = self.stream.read_i32_le().await?;
+        let kind = self.stream.read_i32_le().await?;
+        let mut body = vec![0; length as usize - 8];
+
+        self.stream.read_exact(&mut body).await?;
+        body.truncate(body.len() - 2);
+
+        Ok(Packet { id, kind, body })
+    }
+}
+
+struct Target {
+    address: String,
+    password: String,
+}
+
+pub(crate) struct Client {
+    target: RwLock<Target>,
+    timeout: Duration,
+    connection: Mutex<Option<Connection>>,
+    health: watch::Sender<Health>,
+}
+
+impl Client {
+    pub(crate) fn new<T: Into<String>>(
+        address: T,
+        password: Option<T>,
+        timeout: Duration,
+    ) -> Client {
+        let (health, _) = watch::channel(Health::Disconnected);
+
+        Self {
+            target: RwLock::new(Target {
+                address: address.into(),
+                password: password.map(Into::into).unwrap_or_default(),
+            }),
+            timeout,
+            connection: Mutex::new(None),
+            health,
+        }
+    }
+
+    pub(crate) fn health(&self) -> Health {
+        *self.health.borrow()
+    }
+
+    pub(crate) async fn connect(&self) -> Result<(), Error> {
+        let mut connection = self.connection.lock().await;
+
+        if connection.is_none() {
+            *connection = Some(self.open().await?);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn configure<T: Into<String>>(&self, address: T, password: Option<T>) {
+        let mut connection = self.connection.lock().await;
+
+        *self.target.write().expect("rcon target lock poisoned") = Target {
+            address: address.into(),
+            password: password.map(Into::into).unwrap_or_default(),
+        };
+        *connection = None;
+
+        self.health.send_replace(Health::Disconnected);
+    }
+
+    pub(crate) async fn disconnect(&self) {
+        *self.connection.lock().await = None;
+
+        self.health.send_replace(Health::Disconnected);
+    }
+
+    pub(crate) async fn execute<T: AsRef<str>>(&self, command: T) -> Result<String, Error> {
+        let mut connection = self.connection.lock().await;
+        let reused = connection.is_some();
+
+        if connection.is_none() {
+            *connection = Some(self.open().await?);
+        }
+
+        match self.execute_on(&mut connection, command.as_ref()).await {
+            Ok(body) => Ok(body),
+            // A connection left over from before a server restart, the command
+            // never reached the server so it is safe to send on a fresh one.
+            Err(Failure::Unsent(Error::Io(_))) if reused => {
+                *connection = Some(self.open().await?);
+
+                Ok(self.execute_on(&mut connection, command.as_ref()).await?)
+            }
+            Err(failure) => Err(failure.into()),
+        }
+    }
+
+    async fn execute_on(
+        &self,
+        connection: &mut Option<Connection>,
+        command: &str,
+    ) -> Result<String, Failure> {
+        let Some(current) = connection.as_mut() else {
+            return Err(Failure::Unsent(Error::Protocol("not connected")));
+        };
+
+        let result = match time::timeout(self.timeout, current.execute(command)).await {
+            Ok(result) => result,
+            Err(_) => Err(Failure::Unanswered(Error::Timeout, None)),
+        };
+
+        if result.is_err() {
+            *connection = None;
+
+            self.health.send_replace(Health::Disconnected);
+        }
+
+        match result {
+            // The server may close the connection right after answering, `stop`
+            // does exactly that. The answer is still worth returning.
+            Err(Failure::Unanswered(Error::Io(_), Some(body))) => {
+                Ok(String::from_utf8_lossy(&body).into_owned())
+            }
+            result => result,
+        }
+    }
+
+    async fn open(&self) -> Result<Connection, Error> {
+        let (address, password) = {
+            let target = self.target.read().expect("rcon target lock poisoned");
+
+            (target.address.clone(), target.password.clone())
+        };
+        let result = time::timeout(self.timeout, Connection::open(&address, &password))
+            .await
+            .unwrap_or(Err(Error::Timeout));
+
+        self.health.send_replace(match &result {
+            Ok(_) => Health::Connected,
+            Err(Error::Unauthorized) => Health::Unauthorized,
+            Err(_) => Health::Disconnected,
+        });
+
+        result
+    }
+}
+
+// Keeps the connection warm so the health state reflects reality even when
+// nobody is sending commands, and picks the server up again after a restart.
+pub(crate) async fn monitor(state: Arc<app::State>) {
+    let mut interval = time::interval(Duration::from_secs(10));
+
+    loop {
+        interval.tick().await;
+
+        if !state.properties.read().await.rcon.enabled || state.rcon.health() == Health::Connected {
+            continue;
+        }
+
+        if let Err(error) = state.rcon.connect().await {
+            if state.supervisor.status().state == Phase::Running {
+                println!("Failed to connect to rcon: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const PASSWORD: &str = "secret";
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<(i32, i32, String)> {
+        let length = stream.read_i32_le().await.ok()?;
+        let id = stream.read_i32_le().await.ok()?;
+        let kind = stream.read_i32_le().await.ok()?;
+        let mut body = vec![0; length as usize - 8];
+
+        stream.read_exact(&mut body).await.ok()?;
+        body.truncate(body.len() - 2);
+
+        Some((id, kind, String::from_utf8(body).unwrap()))
+    }
+
+    async fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &[u8]) {
+        let mut data = vec![];
+
+        data.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(body);
+        data.extend_from_slice(&[0, 0]);
+
+        stream.write_all(&data).await.unwrap();
+    }
+
+    // Answers the login like Minecraft does, `false` when the password is wrong.
+    async fn login(stream: &mut TcpStream) -> bool {
+        let (id, kind, password) = read_packet(stream).await.unwrap();
+
+        assert_eq!(kind, PACKET_AUTH);
+
+        let accepted = password == PASSWORD;
+
+        write_packet(stream, if accepted { id } else { -1 }, PACKET_COMMAND, b"").await;
+
+        accepted
+    }
+
+    // Answers commands with `answer` for `limit` commands per connection,
+    // splitting long answers into 4096 byte packets like Minecraft.
+    async fn serve(
+        listener: TcpListener,
+        answer: fn(&str) -> Vec<u8>,
+        limit: usize,
+        commands: Arc<AtomicUsize>,
+    ) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            if !login(&mut stream).await {
+                continue;
+            }
+
+            let mut answered = 0;
+
+            while let Some((id, kind, body)) = read_packet(&mut stream).await {
+                if kind == PACKET_RESPONSE {
+                    write_packet(&mut stream, id, PACKET_RESPONSE, b"").await;
+
+                    continue;
+                }
+
+                commands.fetch_add(1, Ordering::SeqCst);
+
+                if answered == limit {
+                    break;
+                }
+
+                for chunk in answer(&body).chunks(4096) {
+                    write_packet(&mut stream, id, PACKET_RESPONSE, chunk).await;
+                }
+
+                answered += 1;
+            }
+        }
+    }
+
+    async fn mock(answer: fn(&str) -> Vec<u8>, limit: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let commands = Arc::new(AtomicUsize::new(0));
+
+        tokio::spawn(serve(listener, answer, limit, commands.clone()));
+
+        (address, commands)
+    }
+
+    fn echo(command: &str) -> Vec<u8> {
+        command.as_bytes().to_vec()
+    }
+
+    #[tokio::test]
+    async fn rejected_password_is_unauthorized() {
+        let (address, _) = mock(echo, usize::MAX).await;
+        let client = Client::new(address, Some("wrong".to_string()), Duration::from_secs(5));
+
+        assert!(matches!(
+            client.execute("list").await,
+            Err(Error::Unauthorized)
+        ));
+        assert_eq!(client.health(), Health::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn long_answers_are_reassembled() {
+        // Multibyte characters end up split between packets.
+        fn answer(_: &str) -> Vec<u8> {
+            "Игрок ".repeat(1500).into_bytes()
+        }
+
+        let (address, _) = mock(answer, usize::MAX).await;
+        let client = Client::new(address, Some(PASSWORD.to_string()), Duration::from_secs(5));
+        let body = client.execute("list uuids").await.unwrap();
+
+        assert!(answer("").len() > 4096 * 2);
+        assert_eq!(body, "Игрок ".repeat(1500));
+        assert_eq!(client.health(), Health::Connected);
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_the_server_closes() {
+        let (address, commands) = mock(echo, 1).await;
+        let client = Client::new(address, Some(PASSWORD.to_string()), Duration::from_secs(5));
+
+        assert_eq!(client.execute("first").await.unwrap(), "first");
+
+        // The mock hangs up on the next command, the client must notice before
+        // sending it.
+        client.execute("second").await.unwrap_err();
+
+        let sent = commands.load(Ordering::SeqCst);
+
+        time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(client.execute("third").await.unwrap(), "third");
+        assert_eq!(commands.load(Ordering::SeqCst), sent + 1);
+    }
+
+    #[tokio::test]
+    async fn stale_connections_are_replaced_without_resending() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let commands = Arc::new(AtomicUsize::new(0));
+        let counted = commands.clone();
+
+        tokio::spawn(async move {
+            // The first connection is dropped right after logging in, like a
+            // server that restarted.
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            login(&mut stream).await;
+            drop(stream);
+
+            serve(listener, echo, usize::MAX, counted).await;
+        });
+
+        let client = Client::new(address, Some(PASSWORD.to_string()), Duration::from_secs(5));
+
+        client.connect().await.unwrap();
+        time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(client.execute("say hi").await.unwrap(), "say hi");
+        assert_eq!(commands.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn commands_are_not_resent_after_they_went_out() {
+        let (address, commands) = mock(echo, 0).await;
+        let client = Client::new(address, Some(PASSWORD.to_string()), Duration::from_secs(5));
+
+        client.connect().await.unwrap();
+
+        assert!(matches!(
+            client.execute("give Steve diamond").await,
+            Err(Error::Unanswered(_))
+        ));
+        assert_eq!(commands.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn silent_servers_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            login(&mut stream).await;
+
+            while read_packet(&mut stream).await.is_some() {}
+        });
+
+        let client = Client::new(
+            address,
+            Some(PASSWORD.to_string()),
+            Duration::from_millis(200),
+        );
+
+        assert!(matches!(client.execute("list").await, Err(Error::Timeout)));
+        assert_eq!(client.health(), Health::Disconnected);
+    }
+}
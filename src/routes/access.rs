@@ -0,0 +1,153 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Query, Request, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    data::{api_keys::Scope, app, role::Role, users::Account},
+    routes::error::ApiError,
+};
+
+// Browsers cannot set headers on WebSocket requests, so the sockets take the
+// token as a query parameter too. No other route does, it would end up in
+// access logs and the browser history.
+const SOCKET_ROUTES: [&str; 2] = ["/server/console", "/server/changes"];
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+pub(crate) fn bearer(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    let Some(header) = headers.get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+
+    header
+        .to_str()
+        .ok()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(|token| Some(token.trim()))
+        .ok_or(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "expected a bearer token",
+        ))
+}
+
+// Runs in front of everything, including the routes that need no login.
+pub(crate) async fn filter(
+    State(state): State<Arc<app::State>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !state
+        .ip_filter
+        .read()
+        .expect("ip filter lock poisoned")
+        .allows(address.ip(), request.uri().path())
+    {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "address not allowed"));
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Runs in front of every protected route, the account it resolves is handed
+// to `require` and the handlers through the request extensions.
+pub(crate) async fn authenticate(
+    State(state): State<Arc<app::State>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let route = request.extensions().get::<MatchedPath>();
+    let query = query_token(
+        route.map(|route| route.as_str()),
+        request.headers(),
+        request.uri(),
+    );
+    let token = bearer(request.headers())?.or(query.as_deref());
+
+    let Some(account) = state.authenticate(token).await else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "missing or invalid access token",
+        ));
+    };
+
+    request.extensions_mut().insert(account);
+
+    Ok(next.run(request).await)
+}
+
+fn query_token(route: Option<&str>, headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    let upgrade = headers
+        .get(header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+
+    if !upgrade || !route.is_some_and(|route| SOCKET_ROUTES.contains(&route)) {
+        return None;
+    }
+
+    Query::<TokenQuery>::try_from_uri(uri)
+        .ok()
+        .and_then(|Query(query)| query.access_token)
+}
+
+pub(crate) async fn require(
+    State((role, scope)): State<(Role, Scope)>,
+    Extension(account): Extension<Account>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if account.role < role {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("requires the {role} role"),
+        ));
+    }
+
+    if !account.allows(scope) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("requires the {scope} scope"),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn token(route: &str, upgrade: bool) -> Option<String> {
+        let mut headers = HeaderMap::new();
+
+        if upgrade {
+            headers.insert(header::UPGRADE, HeaderValue::from_static("WebSocket"));
+        }
+
+        let uri: Uri = format!("{route}?access_token=secret").parse().unwrap();
+
+        query_token(Some(route), &headers, &uri)
+    }
+
+    #[test]
+    fn only_sockets_take_query_tokens() {
+        assert_eq!(token("/server/console", true).as_deref(), Some("secret"));
+        assert_eq!(token("/server/changes", true).as_deref(), Some("secret"));
+        assert_eq!(token("/server/console", false), None);
+        assert_eq!(token("/server/status", true), None);
+        assert_eq!(token("/auth/keys", false), None);
+    }
+}
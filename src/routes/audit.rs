@@ -0,0 +1,262 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::{self, Body},
+    extract::{ConnectInfo, MatchedPath, Query, Request, State},
+    http::{StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    data::{
+        app,
+        audit::{Entry, Filter, Outcome, Target},
+        server,
+        users::Account,
+    },
+    routes::{error::ApiError, lockouts::ClearQuery},
+};
+
+// What axum lets `Json` read by default.
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub(crate) struct AuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    // RFC 3339, e.g. `2023-09-01T12:00:00+02:00`.
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+    limit: Option<usize>,
+}
+
+pub(crate) async fn record(
+    State((state, action)): State<(Arc<app::State>, &'static str)>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Extension(account): Extension<Account>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let parameter = parameter(&request);
+    let uri = request.uri().clone();
+    let (response, body) = match buffer(action, request).await {
+        Ok((request, body)) => (next.run(request).await, body),
+        Err(response) => (response, None),
+    };
+    let status = response.status();
+    // The handler knows best, but requests that are denied or fail before it
+    // gets that far still name what they were aimed at.
+    let target = response
+        .extensions()
+        .get::<Target>()
+        .map(|target| target.0.clone())
+        .or_else(|| requested(action, &account, parameter, &uri, body.as_ref()));
+
+    state
+        .audit
+        .record(Entry {
+            time: Local::now().into(),
+            actor: account.name,
+            ip: Some(address.ip()),
+            action: action.to_string(),
+            target,
+            result: if status.is_success() {
+                Outcome::Success
+            } else {
+                Outcome::Failure
+            },
+            status: status.as_u16(),
+        })
+        .await;
+
+    response
+}
+
+pub(crate) async fn execute(
+    State(state): State<Arc<app::State>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<(StatusCode, Json<Vec<Entry>>), ApiError> {
+    let filter = Filter {
+        actor: query.actor,
+        action: query.action,
+        since: query.since,
+        until: query.until,
+        limit: query.limit.unwrap_or(500),
+    };
+
+    match state.audit.query(&filter).await {
+        Ok(entries) => Ok((StatusCode::OK, Json(entries))),
+        Err(error) => {
+            println!("Failed to read audit log: {error}");
+
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
+        }
+    }
+}
+
+// The value of the last path segment when the route has a parameter there,
+// like the id in `/auth/keys/:id`.
+fn parameter(request: &Request) -> Option<String> {
+    let route = request.extensions().get::<MatchedPath>()?.as_str();
+
+    if !route.rsplit('/').next()?.starts_with(':') {
+        return None;
+    }
+
+    request
+        .uri()
+        .path()
+        .rsplit('/')
+        .next()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+}
+
+// Reads the JSON body of the actions that name their target in it and puts it
+// back for the handler.
+async fn buffer(action: &str, request: Request) -> Result<(Request, Option<Value>), Response> {
+    if !matches!(action, "server.command" | "server.config" | "keys.create") {
+        return Ok((request, None));
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = body::to_bytes(body, BODY_LIMIT)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+    let value = serde_json::from_slice(&bytes).ok();
+
+    Ok((Request::from_parts(parts, Body::from(bytes)), value))
+}
+
+// The target as the request names it, written like the handlers do.
+fn requested(
+    action: &str,
+    account: &Account,
+    parameter: Option<String>,
+    uri: &Uri,
+    body: Option<&Value>,
+) -> Option<String> {
+    match action {
+        "server.command" => body?
+            .get("command")?
+            .as_str()
+            .map(|command| command.trim().trim_start_matches('/').to_string()),
+        "keys.create" => body?
+            .get("name")?
+            .as_str()
+            .map(|name| name.trim().to_string()),
+        "server.config" => {
+            let mut keys = vec![];
+            let mut extra_keys = vec![];
+
+            for (field, value) in body?.as_object()? {
+                match (field.as_str(), value) {
+                    ("extra", Value::Object(extra)) => extra_keys.extend(extra.keys().cloned()),
+                    ("rcon", Value::Object(rcon)) => {
+                        keys.extend(rcon.keys().map(|name| server::key(&format!("rcon.{name}"))))
+                    }
+                    (field, _) => keys.push(server::key(field)),
+                }
+            }
+
+            keys.append(&mut extra_keys);
+
+            Some(keys.join(", "))
+        }
+        "lockouts.clear" => Query::<ClearQuery>::try_from_uri(uri)
+            .ok()
+            .map(|Query(query)| query.target().0),
+        "totp.enroll" | "totp.confirm" | "totp.disable" => Some(account.name.clone()),
+        _ => parameter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::data::role::Role;
+
+    fn requested_by_bob(action: &str, uri: &str, body: Option<Value>) -> Option<String> {
+        let account = Account {
+            name: "bob".to_string(),
+            role: Role::Viewer,
+            scopes: None,
+            session: None,
+        };
+
+        requested(
+            action,
+            &account,
+            Some("abc123".to_string()),
+            &uri.parse().unwrap(),
+            body.as_ref(),
+        )
+    }
+
+    #[test]
+    fn targets_from_the_body() {
+        assert_eq!(
+            requested_by_bob(
+                "server.command",
+                "/",
+                Some(json!({"command": " /op alice "}))
+            ),
+            Some("op alice".to_string())
+        );
+        assert_eq!(
+            requested_by_bob("keys.create", "/", Some(json!({"name": "ci"}))),
+            Some("ci".to_string())
+        );
+        assert_eq!(
+            requested_by_bob(
+                "server.config",
+                "/",
+                Some(
+                    json!({"motd": "x", "whitelist": true, "rcon": {"port": 1}, "extra": {"mod.key": "1"}})
+                )
+            ),
+            Some("motd, rcon.port, white-list, mod.key".to_string())
+        );
+        // Nothing to go on when the body is not what the handler expects.
+        assert_eq!(requested_by_bob("server.command", "/", None), None);
+        assert_eq!(
+            requested_by_bob("keys.create", "/", Some(json!(["ci"]))),
+            None
+        );
+    }
+
+    #[test]
+    fn targets_from_the_query() {
+        assert_eq!(
+            requested_by_bob(
+                "lockouts.clear",
+                "/auth/lockouts?ip=%3A%3A1&username=carol",
+                None
+            ),
+            Some("ip ::1, account carol".to_string())
+        );
+        assert_eq!(
+            requested_by_bob("lockouts.clear", "/auth/lockouts", None),
+            Some("all".to_string())
+        );
+    }
+
+    #[test]
+    fn targets_from_the_path_and_account() {
+        assert_eq!(
+            requested_by_bob("keys.revoke", "/auth/keys/abc123", None),
+            Some("abc123".to_string())
+        );
+        assert_eq!(
+            requested_by_bob("totp.disable", "/auth/totp", None),
+            Some("bob".to_string())
+        );
+    }
+}
@@ -1,41 +1,182 @@
-use std::sync::Arc;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Local;
 use serde::{Deserialize, Serialize};
 
-use crate::data::app;
+use crate::{
+    data::{
+        app,
+        audit::{Entry, Outcome},
+        session::Session,
+        throttle::Key,
+        totp::Challenge,
+        users::Account,
+    },
+    routes::error::ApiError,
+};
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum Login {
+    Session(Session),
+    // Accounts with totp get a session from `/auth/totp/verify` instead.
+    Challenge(Challenge),
+}
 
 pub(crate) async fn execute(
     State(state): State<Arc<app::State>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     Json(payload): Json<AuthData>,
-) -> Result<(StatusCode, Json<AuthResponse>), StatusCode> {
-    if state.access_token.is_none() || state.encryption_key.is_none() {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+) -> Result<(StatusCode, Json<Login>), ApiError> {
+    if state.users.is_empty() {
+        return Err(StatusCode::NOT_IMPLEMENTED.into());
+    }
+
+    let username = payload.username.clone();
+    let keys = [Key::Ip(address.ip()), Key::Account(username.clone())];
+
+    if let Err(wait) = state.throttle.attempt(&keys).await {
+        record_failure(
+            &state,
+            "auth.login",
+            &username,
+            address.ip(),
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+        .await;
+
+        return Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "too many login attempts, retry in {}s",
+                wait.as_secs_f64().ceil()
+            ),
+        ));
     }
 
-    let access_token = state.access_token.as_ref().unwrap();
-    let encryption_key = state.encryption_key.as_ref().unwrap();
-    let password = payload.password;
+    // The password hash is deliberately slow, keep it off the async workers.
+    let account = {
+        let state = state.clone();
 
-    if &crate::utils::encode_password(password, encryption_key.clone()) == access_token {
+        tokio::task::spawn_blocking(move || {
+            state.users.verify(&payload.username, &payload.password)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+
+    let Some(account) = account else {
+        println!("Failed login for {username} from {}", address.ip());
+
+        state.throttle.failure(&keys).await;
+        record_failure(
+            &state,
+            "auth.login",
+            &username,
+            address.ip(),
+            StatusCode::UNAUTHORIZED,
+        )
+        .await;
+
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid username or password",
+        ));
+    };
+
+    // The lockout stays until the second step is done as well, or the
+    // password alone would reset it between guesses of the code.
+    if state.totp.is_enabled(&account.name).await {
         return Ok((
-            StatusCode::CREATED,
-            Json(AuthResponse {
-                access_token: access_token.clone(),
-            }),
+            StatusCode::ACCEPTED,
+            Json(Login::Challenge(state.totp.challenge(&account.name).await)),
         ));
     }
 
-    Err(StatusCode::UNAUTHORIZED)
+    // Only the account is forgiven, otherwise logging into an account of
+    // their own would let anyone reset the lockout of their address.
+    state
+        .throttle
+        .success(&[Key::Account(account.name.clone())])
+        .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(Login::Session(state.sessions.issue(&account.name))),
+    ))
 }
 
-// the input to our `create_user` handler
-#[derive(Serialize)]
-pub(crate) struct AuthResponse {
-    access_token: String,
+pub(crate) async fn refresh(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+) -> Result<(StatusCode, Json<Session>), ApiError> {
+    let claims = account.session.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    state.sessions.revoke(&claims).await.map_err(|error| {
+        println!("Failed to save revoked sessions: {error}");
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(state.sessions.issue(&account.name)),
+    ))
+}
+
+pub(crate) async fn logout(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+) -> Result<StatusCode, ApiError> {
+    let claims = account.session.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    state.sessions.revoke(&claims).await.map_err(|error| {
+        println!("Failed to save revoked sessions: {error}");
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Nobody is logged in yet, the account that was tried stands in for the actor.
+pub(crate) async fn record_failure(
+    state: &app::State,
+    action: &str,
+    user: &str,
+    ip: IpAddr,
+    status: StatusCode,
+) {
+    state
+        .audit
+        .record(Entry {
+            time: Local::now().into(),
+            actor: user.to_string(),
+            ip: Some(ip),
+            action: action.to_string(),
+            target: Some(user.to_string()),
+            result: Outcome::Failure,
+            status: status.as_u16(),
+        })
+        .await;
 }
 
 #[derive(Deserialize)]
 pub(crate) struct AuthData {
+    // Logins from before multiple accounts only sent the password.
+    #[serde(default = "default_username")]
+    username: String,
     password: String,
 }
+
+fn default_username() -> String {
+    "admin".to_string()
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        api_keys::{ApiKey, Scope},
+        app,
+        audit::Target,
+        users::Account,
+    },
+    routes::error::ApiError,
+};
+
+// A hundred years, far enough out to mean never and small enough that the
+// expiry time cannot overflow.
+const MAX_EXPIRES_IN: i64 = 100 * 365 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub(crate) struct CreateKey {
+    name: String,
+    scopes: Vec<Scope>,
+    // Seconds until the key expires, keys without it never do.
+    expires_in: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CreatedKey {
+    // Only ever shown here, the panel keeps nothing but a hash of it.
+    key: String,
+    #[serde(flatten)]
+    details: ApiKey,
+}
+
+pub(crate) async fn list(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Vec<ApiKey>>), ApiError> {
+    Ok((StatusCode::OK, Json(state.api_keys.list().await)))
+}
+
+pub(crate) async fn create(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+    Json(payload): Json<CreateKey>,
+) -> Result<(StatusCode, Extension<Target>, Json<CreatedKey>), ApiError> {
+    if account.scopes.is_some() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "api keys cannot create other keys",
+        ));
+    }
+
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "name is empty"));
+    }
+
+    let expires_at = match payload.expires_in {
+        Some(expires_in) => Some(expiry(Local::now().into(), expires_in)?),
+        None => None,
+    };
+
+    let (details, key) = state
+        .api_keys
+        .create(
+            payload.name.trim().to_string(),
+            payload.scopes,
+            account.role,
+            account.name,
+            expires_at,
+        )
+        .await
+        .map_err(|error| {
+            println!("Failed to save api keys: {error}");
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(Target(format!("{} ({})", details.id, details.name))),
+        Json(CreatedKey { key, details }),
+    ))
+}
+
+fn expiry(now: DateTime<FixedOffset>, expires_in: i64) -> Result<DateTime<FixedOffset>, ApiError> {
+    if !(1..=MAX_EXPIRES_IN).contains(&expires_in) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("expires_in must be between 1 and {MAX_EXPIRES_IN}"),
+        ));
+    }
+
+    now.checked_add_signed(Duration::seconds(expires_in))
+        .ok_or(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "expires_in is out of range",
+        ))
+}
+
+pub(crate) async fn revoke(
+    State(state): State<Arc<app::State>>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Extension<Target>), ApiError> {
+    match state.api_keys.revoke(&id).await {
+        Ok(true) => Ok((StatusCode::NO_CONTENT, Extension(Target(id)))),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into()),
+        Err(error) => {
+            println!("Failed to save api keys: {error}");
+
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+
+    use super::*;
+
+    #[test]
+    fn expiry_is_bounded() {
+        let now = Local::now().into();
+
+        assert_eq!(
+            expiry(now, 60).expect("in range"),
+            now + Duration::seconds(60)
+        );
+        assert!(expiry(now, MAX_EXPIRES_IN).is_ok());
+
+        for expires_in in [0, -1, MAX_EXPIRES_IN + 1, i64::MAX, i64::MIN] {
+            let error = expiry(now, expires_in).expect_err("out of range");
+
+            assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+        }
+    }
+}
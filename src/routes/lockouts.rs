@@ -0,0 +1,85 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        app,
+        audit::Target,
+        throttle::{Key, Lockout},
+    },
+    routes::error::ApiError,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct ClearQuery {
+    ip: Option<IpAddr>,
+    username: Option<String>,
+}
+
+impl ClearQuery {
+    fn keys(&self) -> Vec<Key> {
+        self.ip
+            .map(Key::Ip)
+            .into_iter()
+            .chain(self.username.clone().map(Key::Account))
+            .collect()
+    }
+
+    pub(crate) fn target(&self) -> Target {
+        let keys = self.keys();
+
+        if keys.is_empty() {
+            return Target("all".to_string());
+        }
+
+        Target(
+            keys.iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Cleared {
+    cleared: usize,
+}
+
+pub(crate) async fn list(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Vec<Lockout>>), ApiError> {
+    Ok((StatusCode::OK, Json(state.throttle.list().await)))
+}
+
+// Without a query every entry is cleared.
+pub(crate) async fn clear(
+    State(state): State<Arc<app::State>>,
+    Query(query): Query<ClearQuery>,
+) -> Result<(StatusCode, Extension<Target>, Json<Cleared>), ApiError> {
+    let keys = query.keys();
+
+    let cleared = if keys.is_empty() {
+        state.throttle.clear(None).await
+    } else {
+        let mut cleared = 0;
+
+        for key in &keys {
+            cleared += state.throttle.clear(Some(key)).await;
+        }
+
+        cleared
+    };
+
+    Ok((
+        StatusCode::OK,
+        Extension(query.target()),
+        Json(Cleared { cleared }),
+    ))
+}
@@ -1,2 +1,8 @@
+pub(crate) mod access;
+pub(crate) mod audit;
 pub(crate) mod auth;
+pub(crate) mod error;
+pub(crate) mod keys;
+pub(crate) mod lockouts;
 pub(crate) mod server;
+pub(crate) mod totp;
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::data::app;
+
+// Sends `{"changed": "properties" | "user_cache" | "mods"}` whenever one of
+// them is reloaded, so clients know to fetch it again.
+pub(crate) async fn execute(
+    State(state): State<Arc<app::State>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| handle(socket, state))
+}
+
+async fn handle(mut socket: WebSocket, state: Arc<app::State>) {
+    let mut changes = state.changes.subscribe();
+
+    loop {
+        tokio::select! {
+            change = changes.recv() => {
+                let change = match change {
+                    Ok(change) => change,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                let message = serde_json::to_string(&change).expect("failed to serialize change");
+
+                if socket.send(Message::Text(message)).await.is_err() {
+                    return;
+                }
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        api_keys::Scope, app, audit::Target, commands::CommandPolicy, role::Role, users::Account,
+    },
+    parsers::formatting,
+    routes::error::ApiError,
+    transport::{self, Transport},
+};
+
+// Also required of the console socket, which runs commands the same way.
+pub(crate) const ROLE: Role = Role::Moderator;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ResponseFormat {
+    #[default]
+    Plain,
+    Html,
+    Raw,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CommandQuery {
+    #[serde(default)]
+    format: ResponseFormat,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CommandData {
+    command: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CommandResponse {
+    command: String,
+    transport: Transport,
+    response: String,
+}
+
+pub(crate) async fn execute(
+    State(state): State<Arc<app::State>>,
+    Query(query): Query<CommandQuery>,
+    Extension(account): Extension<Account>,
+    Json(payload): Json<CommandData>,
+) -> Result<(StatusCode, Extension<Target>, Json<CommandResponse>), ApiError> {
+    let command = payload.command.trim().trim_start_matches('/').to_string();
+
+    check(&state.commands, &account, &command)?;
+
+    let (transport, response) = transport::execute(&state, &command).await?;
+
+    let response = match query.format {
+        ResponseFormat::Plain => formatting::strip(response),
+        ResponseFormat::Html => formatting::to_html(response),
+        ResponseFormat::Raw => response,
+    };
+
+    Ok((
+        StatusCode::OK,
+        Extension(Target(command.clone())),
+        Json(CommandResponse {
+            command,
+            transport,
+            response,
+        }),
+    ))
+}
+
+// The role and scope are checked by the route already, the socket has only
+// this.
+pub(crate) fn check(
+    commands: &CommandPolicy,
+    account: &Account,
+    command: &str,
+) -> Result<(), ApiError> {
+    if command.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "command is empty"));
+    }
+
+    if account.role < ROLE {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("requires the {ROLE} role"),
+        ));
+    }
+
+    if !account.allows(Scope::ConsoleWrite) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("requires the {} scope", Scope::ConsoleWrite),
+        ));
+    }
+
+    if !commands.is_allowed(account.role, command) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("not allowed to run `{command}`"),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(role: Role, scopes: Option<Vec<Scope>>) -> Account {
+        Account {
+            name: "bob".to_string(),
+            role,
+            scopes,
+            session: None,
+        }
+    }
+
+    fn status(account: &Account, command: &str) -> Option<StatusCode> {
+        check(&CommandPolicy::default(), account, command)
+            .err()
+            .map(|error| error.status())
+    }
+
+    #[test]
+    fn viewers_cannot_run_commands() {
+        assert_eq!(
+            status(&account(Role::Viewer, None), "list"),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(status(&account(Role::Moderator, None), "list"), None);
+    }
+
+    #[test]
+    fn empty_commands_are_rejected() {
+        assert_eq!(
+            status(&account(Role::Admin, None), ""),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn keys_need_the_write_scope() {
+        let reader = account(Role::Admin, Some(vec![Scope::ConsoleRead]));
+        let writer = account(Role::Admin, Some(vec![Scope::ConsoleWrite]));
+
+        assert_eq!(status(&reader, "list"), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status(&writer, "list"), None);
+    }
+
+    #[test]
+    fn the_policy_applies_to_the_role() {
+        assert_eq!(
+            status(&account(Role::Moderator, None), "stop"),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(status(&account(Role::Admin, None), "stop"), None);
+    }
+}
@@ -1,26 +1,466 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::fs;
 
-use crate::data::{app, server};
+use crate::{
+    data::{
+        api_keys::Scope,
+        app,
+        audit::Target,
+        role::Role,
+        server::{self, Properties},
+        users::Account,
+    },
+    parsers::properties::Document,
+    rcon::Health,
+    routes::error::ApiError,
+    transport, utils,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct ConfigQuery {
+    // Secrets are masked unless an admin asks for them.
+    #[serde(default)]
+    reveal: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Updated {
+    changed: Vec<String>,
+    // The server reads the file on startup, anything not applied through a
+    // command only takes effect on the next one.
+    restart_required: Vec<String>,
+}
 
 pub(crate) async fn execute(
     State(state): State<Arc<app::State>>,
-    headers: HeaderMap,
-) -> Result<(StatusCode, Json<server::Properties>), StatusCode> {
-    let authorization = headers.get("Authorization");
+    Query(query): Query<ConfigQuery>,
+    Extension(account): Extension<Account>,
+) -> Result<(StatusCode, Json<Properties>), ApiError> {
+    let properties = state.properties.read().await;
+
+    Ok((
+        StatusCode::OK,
+        Json(view(
+            &properties,
+            query.reveal,
+            !state.users.is_empty(),
+            &account,
+        )?),
+    ))
+}
+
+fn view(
+    properties: &Properties,
+    reveal: bool,
+    has_accounts: bool,
+    account: &Account,
+) -> Result<Properties, ApiError> {
+    if !reveal {
+        return Ok(properties.redacted());
+    }
+
+    // Without any accounts everyone is an admin, so nobody gets to see them.
+    if !has_accounts || account.role < Role::Admin || !account.allows(Scope::ServerConfig) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "secrets are only shown to signed in admins",
+        ));
+    }
+
+    Ok(properties.clone())
+}
+
+// Takes the fields of `GET /server/config` to change, `rcon` and `extra` are
+// merged with the keys already there.
+pub(crate) async fn update(
+    State(state): State<Arc<app::State>>,
+    Json(patch): Json<Map<String, Value>>,
+) -> Result<(StatusCode, Extension<Target>, Json<Updated>), ApiError> {
+    let path = state.path.join("server.properties");
+    let _lock = state.properties_file.lock().await;
+
+    // Read again rather than trusting `state.properties`, someone may have
+    // edited the file by hand since.
+    let mut document = Document::parse(fs::read_to_string(&path).await.map_err(|error| {
+        println!("Failed to read server.properties: {error}");
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    let current = Properties::from_document(&document);
+    let (updated, changes) = merge(&current, patch)?;
+
+    if !changes.is_empty() {
+        for (key, value) in &changes {
+            document.set(key, value.clone());
+        }
+
+        utils::write_atomic(&path, document.to_string())
+            .await
+            .map_err(|error| {
+                println!("Failed to write server.properties: {error}");
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        state.set_properties(updated).await;
+    }
+
+    let running = state.supervisor.pid().is_some() || state.rcon.health() == Health::Connected;
+    let mut restart_required = vec![];
+
+    for (key, value) in &changes {
+        if !running {
+            break;
+        }
+
+        let applied = match command(key, value) {
+            Some(command) => transport::execute(&state, &command).await.is_ok(),
+            None => false,
+        };
+
+        if !applied {
+            restart_required.push(key.clone());
+        }
+    }
+
+    let changed: Vec<String> = changes.into_iter().map(|(key, _)| key).collect();
+
+    Ok((
+        StatusCode::OK,
+        Extension(Target(changed.join(", "))),
+        Json(Updated {
+            changed,
+            restart_required,
+        }),
+    ))
+}
+
+// Applies the patch to the current properties and returns them with the
+// entries that actually changed.
+fn merge(
+    current: &Properties,
+    patch: Map<String, Value>,
+) -> Result<(Properties, Vec<(String, String)>), ApiError> {
+    let mut merged = serde_json::to_value(current).expect("failed to serialize properties");
+    let fields = merged
+        .as_object_mut()
+        .expect("properties always serialize to an object");
+
+    // Sending back what `GET` returned leaves the masked secrets alone.
+    for (field, value) in patch {
+        match (fields.get_mut(&field), value) {
+            (Some(Value::Object(extra)), Value::Object(changes)) if field == "extra" => extra
+                .extend(
+                    changes
+                        .into_iter()
+                        .filter(|(key, value)| !Properties::is_mask(key, value)),
+                ),
+            (Some(Value::Object(rcon)), Value::Object(changes)) if field == "rcon" => {
+                for (name, value) in changes {
+                    let Some(slot) = rcon.get_mut(&name) else {
+                        return Err(ApiError::new(
+                            StatusCode::BAD_REQUEST,
+                            format!("unknown field `rcon.{name}`"),
+                        ));
+                    };
+
+                    if !Properties::is_mask(&server::key(&format!("rcon.{name}")), &value) {
+                        *slot = value;
+                    }
+                }
+            }
+            (Some(_), value) if Properties::is_mask(&server::key(&field), &value) => {}
+            (Some(slot), value) => *slot = value,
+            (None, _) => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("unknown field `{field}`"),
+                ))
+            }
+        }
+    }
+
+    let updated: Properties = serde_json::from_value(merged)
+        .map_err(|error| ApiError::new(StatusCode::BAD_REQUEST, error.to_string()))?;
+
+    let before: HashMap<String, String> = current.entries().into_iter().collect();
+    let changes: Vec<(String, String)> = updated
+        .entries()
+        .into_iter()
+        .filter(|(key, value)| before.get(key) != Some(value))
+        .collect();
+    let keys: Vec<&str> = changes.iter().map(|(key, _)| key.as_str()).collect();
+
+    updated
+        .validate(&keys)
+        .map_err(|error| ApiError::new(StatusCode::BAD_REQUEST, error))?;
+
+    Ok((updated, changes))
+}
+
+// The few keys a running server can be told about.
+fn command(key: &str, value: &str) -> Option<String> {
+    match key {
+        "difficulty" => Some(format!("difficulty {value}")),
+        "gamemode" => Some(format!("defaultgamemode {value}")),
+        "white-list" => Some(format!(
+            "whitelist {}",
+            if value == "true" { "on" } else { "off" }
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use serde_json::json;
+
+    use super::*;
+
+    fn patch(value: Value) -> Map<String, Value> {
+        value.as_object().expect("patches are objects").clone()
+    }
+
+    fn status(result: Result<(Properties, Vec<(String, String)>), ApiError>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(error) => error.into_response().status(),
+        }
+    }
+
+    const SECRETS: &str = "enable-rcon=true\nrcon.password=hunter2\nresource-pack-sha1=\
+        0123456789abcdef0123456789abcdef01234567\nmymod.api-token=abc\nmotd=Hi\n";
+
+    fn account(role: Role, scopes: Option<Vec<Scope>>) -> Account {
+        Account {
+            name: "someone".to_string(),
+            role,
+            scopes,
+            session: None,
+        }
+    }
 
-    if state.access_token.is_none()
-        || state.access_token.clone().is_some_and(|token| {
-            authorization.is_some_and(|header| header.to_str().unwrap() == token)
-        })
-    {
-        return Ok((StatusCode::OK, Json(state.properties.clone())));
+    #[test]
+    fn secrets_are_masked_unless_revealed() {
+        let properties = Properties::new(SECRETS);
+
+        for role in [Role::Viewer, Role::Moderator, Role::Operator, Role::Admin] {
+            let shown = view(&properties, false, true, &account(role, None)).expect("allowed");
+
+            assert_eq!(shown.rcon.password.as_deref(), Some(server::REDACTED));
+            assert_eq!(shown.motd, "Hi");
+        }
+
+        let shown = view(&properties, true, true, &account(Role::Admin, None)).expect("allowed");
+
+        assert_eq!(shown.rcon.password.as_deref(), Some("hunter2"));
+        assert_eq!(
+            shown.extra.get("mymod.api-token").map(String::as_str),
+            Some("abc")
+        );
     }
 
-    Err(StatusCode::UNAUTHORIZED)
+    #[test]
+    fn only_signed_in_admins_may_reveal() {
+        let properties = Properties::new(SECRETS);
+        let forbidden = |has_accounts, account: Account| {
+            view(&properties, true, has_accounts, &account)
+                .err()
+                .map(|error| error.into_response().status())
+        };
+
+        for role in [Role::Viewer, Role::Moderator, Role::Operator] {
+            assert_eq!(
+                forbidden(true, account(role, None)),
+                Some(StatusCode::FORBIDDEN)
+            );
+        }
+
+        // Nobody is signed in when there are no accounts.
+        assert_eq!(
+            forbidden(false, account(Role::Admin, None)),
+            Some(StatusCode::FORBIDDEN)
+        );
+        // An admin's api key still needs the scope.
+        assert_eq!(
+            forbidden(true, account(Role::Admin, Some(vec![Scope::ModsRead]))),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            forbidden(true, account(Role::Admin, Some(vec![Scope::ServerConfig]))),
+            None
+        );
+    }
+
+    #[test]
+    fn sending_back_the_masked_body_changes_nothing() {
+        let current = Properties::new(SECRETS);
+        let shown = serde_json::to_value(current.redacted()).unwrap();
+        let (updated, changes) = merge(&current, patch(shown.clone())).expect("valid patch");
+
+        assert!(changes.is_empty(), "{changes:?}");
+        assert_eq!(updated.rcon.password.as_deref(), Some("hunter2"));
+
+        let mut edited = shown;
+
+        edited["motd"] = json!("Changed");
+
+        let (updated, changes) = merge(&current, patch(edited)).expect("valid patch");
+
+        assert_eq!(changes, vec![("motd".to_string(), "Changed".to_string())]);
+        assert_eq!(updated.rcon.password.as_deref(), Some("hunter2"));
+        assert_eq!(
+            updated.resource_pack_sha1.as_deref(),
+            current.resource_pack_sha1.as_deref()
+        );
+        assert_eq!(
+            updated.extra.get("mymod.api-token").map(String::as_str),
+            Some("abc")
+        );
+    }
+
+    #[test]
+    fn secrets_can_still_be_replaced() {
+        let current = Properties::new(SECRETS);
+        let (updated, changes) = merge(
+            &current,
+            patch(json!({"rcon": {"password": "new"}, "extra": {"mymod.api-token": ""}})),
+        )
+        .expect("valid patch");
+
+        assert_eq!(updated.rcon.password.as_deref(), Some("new"));
+        assert_eq!(
+            changes,
+            vec![
+                ("rcon.password".to_string(), "new".to_string()),
+                ("mymod.api-token".to_string(), String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn only_changed_entries_are_returned() {
+        let current = Properties::new("motd=old\nview-distance=10\nmodded.key=1\n");
+        let (updated, changes) = merge(
+            &current,
+            patch(json!({"motd": "new", "view_distance": 10, "extra": {"modded.key": "2"}})),
+        )
+        .expect("valid patch");
+
+        assert_eq!(updated.motd, "new");
+        assert_eq!(
+            changes,
+            vec![
+                ("motd".to_string(), "new".to_string()),
+                ("modded.key".to_string(), "2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn untouched_keys_out_of_range_are_kept() {
+        // Modded servers often run past the vanilla limits.
+        let current = Properties::new("view-distance=64\nop-permission-level=0\n");
+        let (updated, changes) =
+            merge(&current, patch(json!({"motd": "hello"}))).expect("valid patch");
+
+        assert_eq!(updated.view_distance, 64);
+        assert_eq!(changes, vec![("motd".to_string(), "hello".to_string())]);
+    }
+
+    #[test]
+    fn changed_keys_out_of_range_are_rejected() {
+        let current = Properties::new("view-distance=64\n");
+
+        assert_eq!(
+            status(merge(&current, patch(json!({"view_distance": 40})))),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(merge(&current, patch(json!({"op_permission_level": 5})))),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(merge(&current, patch(json!({"view_distance": 12})))),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn new_extra_keys_must_be_writable() {
+        let current = Properties::new("broken\\ key=1\n");
+
+        assert_eq!(
+            status(merge(&current, patch(json!({"extra": {"bad=key": "1"}})))),
+            StatusCode::BAD_REQUEST
+        );
+        // Already in the file, so it is not this request's problem.
+        assert_eq!(
+            status(merge(&current, patch(json!({"motd": "hi"})))),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn rcon_is_merged_field_by_field() {
+        let current = Properties::new("enable-rcon=true\nrcon.password=secret\nrcon.port=25575\n");
+        let (updated, changes) = merge(
+            &current,
+            patch(json!({"rcon": {"password": server::REDACTED, "port": 25580}})),
+        )
+        .expect("valid patch");
+
+        assert!(updated.rcon.enabled);
+        assert_eq!(updated.rcon.password.as_deref(), Some("secret"));
+        assert_eq!(
+            changes,
+            vec![("rcon.port".to_string(), "25580".to_string())]
+        );
+
+        assert_eq!(
+            status(merge(&current, patch(json!({"rcon": {"pasword": "x"}})))),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn renamed_fields_change_their_keys() {
+        let (_, changes) = merge(
+            &Properties::default(),
+            patch(json!({"ip": "10.0.0.2", "port": 25566, "whitelist": true})),
+        )
+        .expect("valid patch");
+
+        assert_eq!(
+            changes,
+            vec![
+                ("server-ip".to_string(), "10.0.0.2".to_string()),
+                ("server-port".to_string(), "25566".to_string()),
+                ("white-list".to_string(), "true".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_and_wrong_types_are_rejected() {
+        let current = Properties::default();
+
+        assert_eq!(
+            status(merge(&current, patch(json!({"view_distanse": 12})))),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(merge(&current, patch(json!({"view_distance": "far"})))),
+            StatusCode::BAD_REQUEST
+        );
+    }
 }
@@ -0,0 +1,135 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, State,
+    },
+    http::StatusCode,
+    response::Response,
+    Extension,
+};
+use chrono::Local;
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    data::{
+        app,
+        audit::{Entry, Outcome},
+        users::Account,
+    },
+    parsers::formatting,
+    routes::server::command,
+    transport,
+};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ConsoleMessage {
+    Line { line: String },
+    Response { command: String, response: String },
+    Error { message: String },
+}
+
+pub(crate) async fn execute(
+    State(state): State<Arc<app::State>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Extension(account): Extension<Account>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| handle(socket, state, account, address.ip()))
+}
+
+async fn handle(mut socket: WebSocket, state: Arc<app::State>, account: Account, ip: IpAddr) {
+    let (history, mut lines) = state.supervisor.console().subscribe().await;
+
+    for line in history {
+        if send(&mut socket, ConsoleMessage::Line { line })
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+
+    loop {
+        let message = tokio::select! {
+            line = lines.recv() => match line {
+                Ok(line) => ConsoleMessage::Line { line },
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(command))) => run_command(&state, &account, ip, command).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        };
+
+        if send(&mut socket, message).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: ConsoleMessage) -> Result<(), axum::Error> {
+    let message = serde_json::to_string(&message).expect("failed to serialize console message");
+
+    socket.send(Message::Text(message)).await
+}
+
+async fn run_command(
+    state: &app::State,
+    account: &Account,
+    ip: IpAddr,
+    command: String,
+) -> ConsoleMessage {
+    let command = command.trim().trim_start_matches('/').to_string();
+
+    let (status, message) = match command::check(&state.commands, account, &command) {
+        Err(error) => (
+            error.status(),
+            ConsoleMessage::Error {
+                message: error.message().to_string(),
+            },
+        ),
+        Ok(()) => match transport::execute(state, &command).await {
+            Ok((_, response)) => (
+                StatusCode::OK,
+                ConsoleMessage::Response {
+                    command: command.clone(),
+                    response: formatting::strip(response),
+                },
+            ),
+            Err(error) => (
+                error.status_code(),
+                ConsoleMessage::Error {
+                    message: error.to_string(),
+                },
+            ),
+        },
+    };
+
+    state
+        .audit
+        .record(Entry {
+            time: Local::now().into(),
+            actor: account.name.clone(),
+            ip: Some(ip),
+            action: "console.command".to_string(),
+            target: (!command.is_empty()).then_some(command),
+            result: if status.is_success() {
+                Outcome::Success
+            } else {
+                Outcome::Failure
+            },
+            status: status.as_u16(),
+        })
+        .await;
+
+    message
+}
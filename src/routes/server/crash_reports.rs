@@ -0,0 +1,94 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+    analyzer::{self, Suspect},
+    data::app,
+};
+
+#[derive(Serialize)]
+pub(crate) struct CrashReportFile {
+    name: String,
+    size: u64,
+    #[serde(with = "crate::data::date_format::user")]
+    modified: DateTime<FixedOffset>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CrashReport {
+    name: String,
+    content: String,
+    suspects: Vec<Suspect>,
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.starts_with('.')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '.' | '-' | '_'))
+}
+
+pub(crate) async fn execute(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Vec<CrashReportFile>>), StatusCode> {
+    let mut reports = vec![];
+    let Ok(mut folder) = fs::read_dir(state.path.join("crash-reports")).await else {
+        return Ok((StatusCode::OK, Json(reports)));
+    };
+
+    while let Ok(Some(entry)) = folder.next_entry().await {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let modified: DateTime<Local> = metadata
+            .modified()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into();
+
+        reports.push(CrashReportFile {
+            name: entry.file_name().to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified: modified.into(),
+        });
+    }
+
+    reports.sort_by_key(|report| Reverse(report.modified));
+
+    Ok((StatusCode::OK, Json(reports)))
+}
+
+pub(crate) async fn read(
+    State(state): State<Arc<app::State>>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<CrashReport>), StatusCode> {
+    if !is_valid_name(&name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let content = fs::read_to_string(state.path.join("crash-reports").join(&name))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let suspects = analyzer::analyze(&content, &state.mods.lock().await);
+
+    Ok((
+        StatusCode::OK,
+        Json(CrashReport {
+            name,
+            content,
+            suspects,
+        }),
+    ))
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::{
+    analyzer::{self, Suspect},
+    data::app,
+    utils,
+};
+
+// debug.log easily grows to hundreds of megabytes, only the end of it is
+// interesting after a crash.
+const DEBUG_LOG_LIMIT: u64 = 4 * 1024 * 1024;
+
+#[derive(Serialize)]
+pub(crate) struct Log {
+    name: String,
+    content: String,
+    suspects: Vec<Suspect>,
+}
+
+pub(crate) async fn debug(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Log>), StatusCode> {
+    let content = utils::read_tail(state.path.join("logs/debug.log"), DEBUG_LOG_LIMIT)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let suspects = analyzer::analyze(&content, &state.mods.lock().await);
+
+    Ok((
+        StatusCode::OK,
+        Json(Log {
+            name: "debug.log".to_string(),
+            content,
+            suspects,
+        }),
+    ))
+}
@@ -1,4 +1,11 @@
+pub(crate) mod changes;
+pub(crate) mod command;
 pub(crate) mod config;
+pub(crate) mod console;
+pub(crate) mod crash_reports;
+pub(crate) mod logs;
 pub(crate) mod mods;
 pub(crate) mod players;
+pub(crate) mod power;
+pub(crate) mod rcon;
 pub(crate) mod restart;
@@ -3,29 +3,19 @@ use std::{io::Cursor, sync::Arc};
 use axum::{
     body::Bytes,
     extract::{Multipart, State},
-    http::{HeaderMap, StatusCode},
-    Json,
+    http::StatusCode,
+    Extension, Json,
 };
 use tokio::fs;
 
 use crate::{
-    data::app,
+    data::{app, audit::Target},
     loaders::{self, forge::Mod},
 };
 
 pub(crate) async fn execute(
     State(state): State<Arc<app::State>>,
-    headers: HeaderMap,
 ) -> Result<(StatusCode, Json<Vec<Mod>>), StatusCode> {
-    let authorization = headers.get("Authorization");
-
-    if state.access_token.clone().is_some_and(|token| {
-        authorization.is_none()
-            || authorization.is_some_and(|header| header.to_str().unwrap() != token)
-    }) {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
-
     Ok((StatusCode::OK, Json(state.mods.lock().await.clone())))
 }
 
@@ -36,18 +26,8 @@ struct ModFile {
 
 pub(crate) async fn upload(
     State(state): State<Arc<app::State>>,
-    headers: HeaderMap,
     mut multipart: Multipart,
-) -> Result<(StatusCode, Json<Vec<Mod>>), StatusCode> {
-    let authorization = headers.get("Authorization");
-
-    if state.access_token.clone().is_some_and(|token| {
-        authorization.is_none()
-            || authorization.is_some_and(|header| header.to_str().unwrap() != token)
-    }) {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
-
+) -> Result<(StatusCode, Extension<Target>, Json<Vec<Mod>>), StatusCode> {
     let mut files = vec![];
 
     while let Some(field) = multipart
@@ -70,6 +50,13 @@ pub(crate) async fn upload(
     }
 
     let mut mods = vec![];
+    let target = Target(
+        files
+            .iter()
+            .map(|file| file.filename.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
 
     for file in files {
         let data = file.data.to_vec();
@@ -79,14 +66,30 @@ pub(crate) async fn upload(
             return Err(StatusCode::BAD_REQUEST);
         }
 
+        let mut loaded = loaders::forge::load_mod(Cursor::new(&data))
+            .await
+            .map_err(|error| {
+                println!("Rejected mod {}: {error}", file.filename);
+
+                StatusCode::BAD_REQUEST
+            })?;
+
         fs::write(state.path.join(format!("mods/{}", file.filename)), &data)
             .await
             .expect("failed to upload a mod");
 
-        mods.append(&mut loaders::forge::load_mod(Cursor::new(data)).await)
+        for r#mod in &mut loaded {
+            r#mod.file = Some(file.filename.clone());
+        }
+
+        mods.append(&mut loaded)
     }
 
     state.mods.lock().await.append(&mut mods);
 
-    Ok((StatusCode::OK, Json(state.mods.lock().await.clone())))
+    Ok((
+        StatusCode::OK,
+        Extension(target),
+        Json(state.mods.lock().await.clone()),
+    ))
 }
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::{
+    data::app,
+    rcon::Health,
+    supervisor::Status,
+    transport::{self, Transport},
+};
+
+#[derive(Serialize)]
+pub(crate) struct ServerStatus {
+    #[serde(flatten)]
+    process: Status,
+    rcon: Health,
+    transport: Option<Transport>,
+}
+
+pub(crate) async fn start(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Status>), StatusCode> {
+    match state.supervisor.start().await {
+        Ok(_) => Ok((StatusCode::OK, Json(state.supervisor.status()))),
+        Err(error) => {
+            println!("Failed to start server: {error}");
+
+            Err(error.status_code())
+        }
+    }
+}
+
+pub(crate) async fn stop(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Status>), StatusCode> {
+    match state.supervisor.stop(&state).await {
+        Ok(_) => Ok((StatusCode::OK, Json(state.supervisor.status()))),
+        Err(error) => {
+            println!("Failed to stop server: {error}");
+
+            Err(error.status_code())
+        }
+    }
+}
+
+pub(crate) async fn kill(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Status>), StatusCode> {
+    match state.supervisor.kill().await {
+        Ok(_) => Ok((StatusCode::OK, Json(state.supervisor.status()))),
+        Err(error) => {
+            println!("Failed to kill server: {error}");
+
+            Err(error.status_code())
+        }
+    }
+}
+
+pub(crate) async fn status(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<ServerStatus>), StatusCode> {
+    Ok((
+        StatusCode::OK,
+        Json(ServerStatus {
+            process: state.supervisor.status(),
+            rcon: state.rcon.health(),
+            transport: transport::select(&state).await,
+        }),
+    ))
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::{data::app, utils};
+
+#[derive(Serialize)]
+pub(crate) struct Provisioned {
+    changed: bool,
+    restart_required: bool,
+}
+
+pub(crate) async fn provision(
+    State(state): State<Arc<app::State>>,
+) -> Result<(StatusCode, Json<Provisioned>), StatusCode> {
+    let _lock = state.properties_file.lock().await;
+    let changed = utils::provision_rcon(&state.path).await.map_err(|error| {
+        println!("Failed to provision rcon: {error}");
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if changed {
+        state.reload_properties().await.map_err(|error| {
+            println!("Failed to reload server.properties: {error}");
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(Provisioned {
+            changed,
+            // The server only reads server.properties on startup.
+            restart_required: changed && state.supervisor.pid().is_some(),
+        }),
+    ))
+}
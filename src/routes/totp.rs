@@ -0,0 +1,167 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    data::{app, audit::Target, session::Session, throttle::Key, totp::Enrolled, users::Account},
+    routes::{auth, error::ApiError},
+};
+
+#[derive(Deserialize)]
+pub(crate) struct CodeData {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct VerifyData {
+    challenge_token: String,
+    // A code from the authenticator app or one of the recovery codes.
+    code: String,
+}
+
+fn failed(error: std::io::Error) -> ApiError {
+    println!("Failed to save totp enrollments: {error}");
+
+    StatusCode::INTERNAL_SERVER_ERROR.into()
+}
+
+pub(crate) async fn enroll(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+) -> Result<(StatusCode, Extension<Target>, Json<Enrolled>), ApiError> {
+    // Api keys and the open panel have no password to put a second factor on.
+    account.session.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    match state.totp.enroll(&account.name).await.map_err(failed)? {
+        Some(enrolled) => Ok((
+            StatusCode::CREATED,
+            Extension(Target(account.name)),
+            Json(enrolled),
+        )),
+        None => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "totp is already enabled",
+        )),
+    }
+}
+
+pub(crate) async fn confirm(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+    Json(payload): Json<CodeData>,
+) -> Result<(StatusCode, Extension<Target>), ApiError> {
+    account.session.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    if !state
+        .totp
+        .confirm(&account.name, &payload.code)
+        .await
+        .map_err(failed)?
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid code or no pending enrollment",
+        ));
+    }
+
+    Ok((StatusCode::NO_CONTENT, Extension(Target(account.name))))
+}
+
+// Turning it off takes a code too, a stolen session alone is not enough.
+pub(crate) async fn disable(
+    State(state): State<Arc<app::State>>,
+    Extension(account): Extension<Account>,
+    Json(payload): Json<CodeData>,
+) -> Result<(StatusCode, Extension<Target>), ApiError> {
+    account.session.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    if !state
+        .totp
+        .verify(&account.name, &payload.code)
+        .await
+        .map_err(failed)?
+    {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "invalid code"));
+    }
+
+    state.totp.disable(&account.name).await.map_err(failed)?;
+
+    Ok((StatusCode::NO_CONTENT, Extension(Target(account.name))))
+}
+
+// For users who lost both their authenticator and their recovery codes.
+pub(crate) async fn reset(
+    State(state): State<Arc<app::State>>,
+    Path(user): Path<String>,
+) -> Result<(StatusCode, Extension<Target>), ApiError> {
+    if !state.totp.disable(&user).await.map_err(failed)? {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    Ok((StatusCode::NO_CONTENT, Extension(Target(user))))
+}
+
+pub(crate) async fn verify(
+    State(state): State<Arc<app::State>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Json(payload): Json<VerifyData>,
+) -> Result<(StatusCode, Json<Session>), ApiError> {
+    let Some(user) = state.totp.challenged(&payload.challenge_token).await else {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid or expired challenge token",
+        ));
+    };
+
+    let keys = [Key::Ip(address.ip()), Key::Account(user.clone())];
+
+    if let Err(wait) = state.throttle.attempt(&keys).await {
+        auth::record_failure(
+            &state,
+            "auth.totp",
+            &user,
+            address.ip(),
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+        .await;
+
+        return Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "too many login attempts, retry in {}s",
+                wait.as_secs_f64().ceil()
+            ),
+        ));
+    }
+
+    if !state
+        .totp
+        .verify(&user, &payload.code)
+        .await
+        .map_err(failed)?
+    {
+        println!("Failed totp code for {user} from {}", address.ip());
+
+        state.throttle.failure(&keys).await;
+        auth::record_failure(
+            &state,
+            "auth.totp",
+            &user,
+            address.ip(),
+            StatusCode::UNAUTHORIZED,
+        )
+        .await;
+
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid code"));
+    }
+
+    state.totp.complete(&payload.challenge_token).await;
+    state.throttle.success(&[Key::Account(user.clone())]).await;
+
+    Ok((StatusCode::CREATED, Json(state.sessions.issue(&user))))
+}
@@ -0,0 +1,713 @@
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    process::{ChildStdin, Command},
+    sync::{watch, Mutex},
+    time,
+};
+
+use crate::{console::Console, data::app, transport};
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    AlreadyRunning,
+    NotRunning,
+    Spawn(io::Error),
+    Stdin(io::Error),
+    Timeout,
+}
+
+impl Error {
+    pub(crate) fn status_code(&self) -> StatusCode {
+        match self {
+            Error::AlreadyRunning | Error::NotRunning => StatusCode::CONFLICT,
+            Error::Spawn(_) | Error::Stdin(_) | Error::Timeout => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::AlreadyRunning => write!(f, "server is already running"),
+            Error::NotRunning => write!(f, "server is not running"),
+            Error::Spawn(error) => write!(f, "failed to spawn server process: {error}"),
+            Error::Stdin(error) => write!(f, "failed to write to server console: {error}"),
+            Error::Timeout => write!(f, "server process did not exit"),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Phase {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    Crashed,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct Status {
+    pub(crate) state: Phase,
+    pub(crate) pid: Option<u32>,
+    pub(crate) uptime: Option<u64>,
+    pub(crate) last_exit_code: Option<i32>,
+    pub(crate) last_crash: Option<Crash>,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct Crash {
+    #[serde(with = "crate::data::date_format::user")]
+    pub(crate) time: DateTime<FixedOffset>,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) report: Option<CrashReport>,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct CrashReport {
+    pub(crate) name: String,
+    pub(crate) content: String,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct CrashPolicy {
+    pub(crate) restart: bool,
+    pub(crate) backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) max_retries: usize,
+    pub(crate) window: Duration,
+}
+
+#[derive(Clone)]
+struct Process {
+    phase: Phase,
+    pid: Option<u32>,
+    started_at: Option<Instant>,
+    last_exit_code: Option<i32>,
+    last_crash: Option<Crash>,
+    crashes: u64,
+}
+
+// The parts that can change while the panel runs.
+struct Settings {
+    command: String,
+    stop_timeout: Duration,
+    crash_policy: CrashPolicy,
+}
+
+pub(crate) struct Supervisor {
+    path: PathBuf,
+    settings: RwLock<Settings>,
+    console: Console,
+    process: Arc<watch::Sender<Process>>,
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    lock: Mutex<()>,
+}
+
+impl Supervisor {
+    pub(crate) fn new<T: Into<String>>(
+        path: PathBuf,
+        command: T,
+        stop_timeout: Duration,
+        crash_policy: CrashPolicy,
+        console: Console,
+    ) -> Supervisor {
+        let (process, _) = watch::channel(Process {
+            phase: Phase::Stopped,
+            pid: None,
+            started_at: None,
+            last_exit_code: None,
+            last_crash: None,
+            crashes: 0,
+        });
+
+        Self {
+            path,
+            settings: RwLock::new(Settings {
+                command: command.into(),
+                stop_timeout,
+                crash_policy,
+            }),
+            console,
+            process: Arc::new(process),
+            stdin: Arc::new(Mutex::new(None)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn configure<T: Into<String>>(
+        &self,
+        command: T,
+        stop_timeout: Duration,
+        crash_policy: CrashPolicy,
+    ) {
+        *self
+            .settings
+            .write()
+            .expect("supervisor settings lock poisoned") = Settings {
+            command: command.into(),
+            stop_timeout,
+            crash_policy,
+        };
+    }
+
+    pub(crate) fn console(&self) -> &Console {
+        &self.console
+    }
+
+    pub(crate) fn pid(&self) -> Option<u32> {
+        self.process.borrow().pid
+    }
+
+    pub(crate) fn status(&self) -> Status {
+        let process = self.process.borrow();
+
+        Status {
+            state: process.phase,
+            pid: process.pid,
+            uptime: process
+                .started_at
+                .map(|started_at| started_at.elapsed().as_secs()),
+            last_exit_code: process.last_exit_code,
+            last_crash: process.last_crash.clone(),
+        }
+    }
+
+    pub(crate) async fn start(&self) -> Result<u32, Error> {
+        let _lock = self.lock.lock().await;
+
+        self.spawn().await
+    }
+
+    pub(crate) async fn stop(&self, state: &app::State) -> Result<(), Error> {
+        let _lock = self.lock.lock().await;
+
+        self.shutdown(state).await
+    }
+
+    pub(crate) async fn restart(&self, state: &app::State) -> Result<u32, Error> {
+        let _lock = self.lock.lock().await;
+
+        match self.shutdown(state).await {
+            Ok(()) | Err(Error::NotRunning) => self.spawn().await,
+            Err(error) => Err(error),
+        }
+    }
+
+    pub(crate) async fn send(&self, line: &str) -> Result<(), Error> {
+        let mut stdin = self.stdin.lock().await;
+        let stdin = stdin.as_mut().ok_or(Error::NotRunning)?;
+
+        stdin
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(Error::Stdin)?;
+        stdin.flush().await.map_err(Error::Stdin)
+    }
+
+    pub(crate) async fn kill(&self) -> Result<(), Error> {
+        let pid = self.pid().ok_or(Error::NotRunning)?;
+
+        self.set_phase(Phase::Stopping);
+
+        signal(pid, libc::SIGKILL);
+
+        if self.wait_for_exit().await {
+            return Ok(());
+        }
+
+        Err(Error::Timeout)
+    }
+
+    async fn spawn(&self) -> Result<u32, Error> {
+        if self.pid().is_some() {
+            return Err(Error::AlreadyRunning);
+        }
+
+        // The server gets its own process group, so signals reach the java
+        // process even when it was started from a shell script.
+        let command = self
+            .settings
+            .read()
+            .expect("supervisor settings lock poisoned")
+            .command
+            .clone();
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(Error::Spawn)?;
+
+        let pid = child.id().ok_or(Error::NotRunning)?;
+        let process = self.process.clone();
+        let stdin = self.stdin.clone();
+        let path = self.path.clone();
+        let started_at = SystemTime::now();
+
+        process.send_modify(|process| {
+            process.phase = Phase::Starting;
+            process.pid = Some(pid);
+            process.started_at = Some(Instant::now());
+        });
+
+        println!("Started server process: {pid}");
+
+        *stdin.lock().await = child.stdin.take();
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(read_output(stdout, process.clone(), self.console.clone()));
+        }
+
+        // Launcher errors and uncaught exceptions only go to stderr, and those
+        // are what an operator needs to see when the server fails to start.
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(read_output(stderr, process.clone(), self.console.clone()));
+        }
+
+        tokio::spawn(async move {
+            let status = child.wait().await;
+
+            match &status {
+                Ok(status) => println!("Server process {pid} exited: {status}"),
+                Err(error) => println!("Failed to wait for server process {pid}: {error}"),
+            }
+
+            *stdin.lock().await = None;
+
+            let exit_code = status.as_ref().ok().and_then(|status| status.code());
+            let phase = exit_phase(process.borrow().phase, status.as_ref().ok());
+            let crash = if phase == Phase::Crashed {
+                Some(Crash {
+                    time: Local::now().into(),
+                    exit_code,
+                    report: latest_crash_report(&path, started_at).await,
+                })
+            } else {
+                None
+            };
+
+            process.send_modify(|process| {
+                process.phase = phase;
+                process.pid = None;
+                process.started_at = None;
+                process.last_exit_code = exit_code;
+
+                if crash.is_some() {
+                    process.last_crash = crash;
+                    process.crashes += 1;
+                }
+            });
+        });
+
+        Ok(pid)
+    }
+
+    async fn shutdown(&self, state: &app::State) -> Result<(), Error> {
+        let pid = self.pid().ok_or(Error::NotRunning)?;
+
+        self.set_phase(Phase::Stopping);
+
+        if let Err(error) = transport::execute(state, "stop").await {
+            println!("Failed to send stop command: {error}");
+        }
+
+        state.rcon.disconnect().await;
+
+        self.escalate(pid).await
+    }
+
+    // Gives the server the stop timeout to exit after it was asked to, then
+    // terminates it, and kills it when it ignores that too.
+    async fn escalate(&self, pid: u32) -> Result<(), Error> {
+        if self.wait_for_exit().await {
+            return Ok(());
+        }
+
+        println!("Server process {pid} did not stop in time, sending SIGTERM");
+
+        signal(pid, libc::SIGTERM);
+
+        if self.wait_for_exit().await {
+            return Ok(());
+        }
+
+        println!("Server process {pid} ignored SIGTERM, sending SIGKILL");
+
+        signal(pid, libc::SIGKILL);
+
+        if self.wait_for_exit().await {
+            return Ok(());
+        }
+
+        Err(Error::Timeout)
+    }
+
+    fn set_phase(&self, phase: Phase) {
+        self.process.send_modify(|process| process.phase = phase);
+    }
+
+    async fn wait_for_exit(&self) -> bool {
+        let mut receiver = self.process.subscribe();
+        let stop_timeout = self
+            .settings
+            .read()
+            .expect("supervisor settings lock poisoned")
+            .stop_timeout;
+
+        let exited = time::timeout(
+            stop_timeout,
+            receiver.wait_for(|process| process.pid.is_none()),
+        )
+        .await;
+
+        matches!(exited, Ok(Ok(_)))
+    }
+}
+
+pub(crate) async fn watchdog(state: Arc<app::State>) {
+    let supervisor = &state.supervisor;
+    let mut receiver = supervisor.process.subscribe();
+    let mut handled = 0;
+    let mut crashes = VecDeque::new();
+
+    loop {
+        match receiver.wait_for(|process| process.crashes > handled).await {
+            Ok(process) => handled = process.crashes,
+            Err(_) => return,
+        }
+
+        // Read on every crash, the policy may have been reloaded since.
+        let policy = supervisor
+            .settings
+            .read()
+            .expect("supervisor settings lock poisoned")
+            .crash_policy;
+
+        if !policy.restart {
+            continue;
+        }
+
+        let Some(delay) = restart_delay(&mut crashes, &policy, Instant::now()) else {
+            println!(
+                "Server crashed {} times in {}s, giving up on automatic restarts",
+                crashes.len(),
+                policy.window.as_secs()
+            );
+
+            continue;
+        };
+
+        println!(
+            "Server crashed, restarting in {}s (attempt {}/{})",
+            delay.as_secs(),
+            crashes.len(),
+            policy.max_retries
+        );
+
+        time::sleep(delay).await;
+
+        if supervisor.process.borrow().phase != Phase::Crashed {
+            continue;
+        }
+
+        if let Err(error) = supervisor.start().await {
+            println!("Failed to restart crashed server: {error}");
+        }
+    }
+}
+
+// Doubles with every crash still inside the window, or gives up once there
+// are more of them than the policy retries.
+fn restart_delay(
+    crashes: &mut VecDeque<Instant>,
+    policy: &CrashPolicy,
+    now: Instant,
+) -> Option<Duration> {
+    crashes.push_back(now);
+
+    while crashes
+        .front()
+        .is_some_and(|crash| now.duration_since(*crash) > policy.window)
+    {
+        crashes.pop_front();
+    }
+
+    if crashes.len() > policy.max_retries {
+        return None;
+    }
+
+    Some(
+        policy
+            .backoff
+            .saturating_mul(1 << (crashes.len() - 1).min(16))
+            .min(policy.max_backoff),
+    )
+}
+
+async fn latest_crash_report(path: &Path, since: SystemTime) -> Option<CrashReport> {
+    let mut folder = fs::read_dir(path.join("crash-reports")).await.ok()?;
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+
+    while let Ok(Some(entry)) = folder.next_entry().await {
+        let Ok(modified) = entry
+            .metadata()
+            .await
+            .and_then(|metadata| metadata.modified())
+        else {
+            continue;
+        };
+
+        if modified >= since && latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            latest = Some((modified, entry.path()));
+        }
+    }
+
+    let (_, path) = latest?;
+    let content = fs::read_to_string(&path).await.ok()?;
+
+    Some(CrashReport {
+        name: path.file_name()?.to_string_lossy().to_string(),
+        content,
+    })
+}
+
+async fn read_output<R: AsyncRead + Unpin>(
+    output: R,
+    process: Arc<watch::Sender<Process>>,
+    console: Console,
+) {
+    let mut lines = BufReader::new(output).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        println!("{line}");
+
+        // Vanilla and Forge both print `Done (12.345s)! For help, type "help"`
+        // once the world is loaded and the server accepts connections.
+        if line.contains("Done (") && line.contains("For help") {
+            process.send_if_modified(|process| {
+                let starting = process.phase == Phase::Starting;
+
+                if starting {
+                    process.phase = Phase::Running;
+                }
+
+                starting
+            });
+        }
+
+        console.push(line).await;
+    }
+}
+
+fn exit_phase(phase: Phase, status: Option<&ExitStatus>) -> Phase {
+    if phase == Phase::Stopping || status.is_some_and(|status| status.success()) {
+        Phase::Stopped
+    } else {
+        Phase::Crashed
+    }
+}
+
+fn signal(pid: u32, signal: libc::c_int) {
+    // A negative pid targets the whole process group created in `spawn`.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STOP_TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn policy() -> CrashPolicy {
+        CrashPolicy {
+            restart: true,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            max_retries: 4,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    fn supervisor(command: &str, console: Console) -> Supervisor {
+        Supervisor::new(
+            std::env::temp_dir(),
+            command,
+            STOP_TIMEOUT,
+            policy(),
+            console,
+        )
+    }
+
+    // What `shutdown` does once the stop command is sent.
+    async fn stop(supervisor: &Supervisor) -> (Result<(), Error>, Duration) {
+        let pid = supervisor.start().await.expect("spawn");
+        let started = Instant::now();
+
+        supervisor.set_phase(Phase::Stopping);
+
+        (supervisor.escalate(pid).await, started.elapsed())
+    }
+
+    #[tokio::test]
+    async fn console_receives_stdout_and_stderr() {
+        let console = Console::new();
+        let supervisor = supervisor("echo from stdout; echo from stderr >&2", console.clone());
+        let (_, mut lines) = console.subscribe().await;
+
+        supervisor.start().await.expect("spawn");
+
+        let mut received = vec![];
+
+        while received.len() < 2 {
+            let line = time::timeout(Duration::from_secs(5), lines.recv())
+                .await
+                .expect("console line")
+                .expect("console open");
+
+            received.push(line);
+        }
+
+        received.sort();
+
+        assert_eq!(received, vec!["from stderr", "from stdout"]);
+        assert!(supervisor.wait_for_exit().await);
+        assert_eq!(supervisor.status().state, Phase::Stopped);
+    }
+
+    #[tokio::test]
+    async fn stopped_servers_are_not_signalled() {
+        let supervisor = supervisor("read line; exit 0", Console::new());
+        let pid = supervisor.start().await.expect("spawn");
+
+        supervisor.set_phase(Phase::Stopping);
+        supervisor.send("stop").await.expect("write stdin");
+
+        let started = Instant::now();
+
+        assert!(supervisor.escalate(pid).await.is_ok());
+        assert!(started.elapsed() < STOP_TIMEOUT);
+
+        let status = supervisor.status();
+
+        assert_eq!(status.state, Phase::Stopped);
+        assert_eq!(status.pid, None);
+        assert_eq!(status.last_exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn servers_that_keep_running_get_sigterm() {
+        let supervisor = supervisor(
+            "trap 'exit 3' TERM; while true; do sleep 0.1; done",
+            Console::new(),
+        );
+        let (result, elapsed) = stop(&supervisor).await;
+
+        assert!(result.is_ok());
+        assert!(elapsed >= STOP_TIMEOUT && elapsed < STOP_TIMEOUT * 2);
+
+        let status = supervisor.status();
+
+        // The trap ran, so it was SIGTERM and not SIGKILL that ended it.
+        assert_eq!(status.state, Phase::Stopped);
+        assert_eq!(status.last_exit_code, Some(3));
+    }
+
+    #[tokio::test]
+    async fn servers_that_ignore_sigterm_get_sigkill() {
+        let supervisor = supervisor(
+            "trap '' TERM; while true; do sleep 0.1; done",
+            Console::new(),
+        );
+        let (result, elapsed) = stop(&supervisor).await;
+
+        assert!(result.is_ok());
+        assert!(elapsed >= STOP_TIMEOUT * 2 && elapsed < STOP_TIMEOUT * 3);
+
+        let status = supervisor.status();
+
+        // Killed by a signal, so there is no exit code.
+        assert_eq!(status.state, Phase::Stopped);
+        assert_eq!(status.last_exit_code, None);
+        assert!(status.last_crash.is_none());
+    }
+
+    #[tokio::test]
+    async fn unexpected_exits_are_crashes() {
+        let supervisor = supervisor("exit 1", Console::new());
+
+        supervisor.start().await.expect("spawn");
+
+        assert!(supervisor.wait_for_exit().await);
+
+        let status = supervisor.status();
+
+        assert_eq!(status.state, Phase::Crashed);
+        assert_eq!(status.last_exit_code, Some(1));
+        assert_eq!(status.last_crash.expect("crash").exit_code, Some(1));
+        assert_eq!(supervisor.process.borrow().crashes, 1);
+    }
+
+    #[test]
+    fn restart_delay_doubles_up_to_the_cap() {
+        let policy = policy();
+        let start = Instant::now();
+        let mut crashes = VecDeque::new();
+        let delays: Vec<Option<Duration>> = (0..5)
+            .map(|crash| restart_delay(&mut crashes, &policy, start + Duration::from_secs(crash)))
+            .collect();
+
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(4)),
+                Some(Duration::from_secs(5)),
+                // More crashes than retries gives up.
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn restart_delay_resets_after_the_window() {
+        let policy = policy();
+        let start = Instant::now();
+        let mut crashes = VecDeque::new();
+
+        for crash in 0..5 {
+            restart_delay(&mut crashes, &policy, start + Duration::from_secs(crash));
+        }
+
+        // The last crash is still in the window, the others are not.
+        assert_eq!(
+            restart_delay(&mut crashes, &policy, start + Duration::from_secs(64)),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            restart_delay(&mut crashes, &policy, start + Duration::from_secs(200)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(crashes.len(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashSet,
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use notify::{RecursiveMode, Watcher};
+use tokio::{fs, io::AsyncWriteExt, sync::mpsc, time};
+
+// Reuses the certificate from an earlier start, so browsers only have to be
+// told to trust it once. A new one is made when it would not cover `names`.
+pub(crate) async fn self_signed(
+    data_dir: &Path,
+    names: &[String],
+) -> io::Result<(PathBuf, PathBuf)> {
+    let folder = data_dir.join("tls");
+    let cert_path = folder.join("cert.pem");
+    let key_path = folder.join("key.pem");
+    let names_path = folder.join("names");
+
+    if cert_path.exists()
+        && key_path.exists()
+        && fs::read_to_string(&names_path)
+            .await
+            .is_ok_and(|data| data == names.join("\n"))
+    {
+        return Ok((cert_path, key_path));
+    }
+
+    let cert = rcgen::generate_simple_self_signed(names).map_err(io::Error::other)?;
+    let cert_pem = cert.serialize_pem().map_err(io::Error::other)?;
+
+    fs::create_dir_all(&folder).await?;
+
+    let mut key = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&key_path)
+        .await?;
+
+    key.write_all(cert.serialize_private_key_pem().as_bytes())
+        .await?;
+    fs::write(&cert_path, cert_pem).await?;
+    fs::write(&names_path, names.join("\n")).await?;
+
+    println!(
+        "Generated a self-signed certificate at {}",
+        cert_path.display()
+    );
+
+    Ok((cert_path, key_path))
+}
+
+// What browsers may reach the panel by, the name in the address bar has to be
+// in the certificate. Listening on every address does not name any of them.
+pub(crate) fn names(bind: IpAddr) -> Vec<String> {
+    let mut names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+
+    if !bind.is_unspecified() {
+        names.push(bind.to_string());
+    }
+
+    names.extend(hostname());
+    let mut seen = HashSet::new();
+
+    names.retain(|name| seen.insert(name.clone()));
+
+    names
+}
+
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+
+    if result != 0 {
+        return None;
+    }
+
+    let end = buffer.iter().position(|byte| *byte == 0)?;
+    let hostname = String::from_utf8(buffer[..end].to_vec()).ok()?;
+
+    (!hostname.is_empty()).then_some(hostname)
+}
+
+// Picks up renewed certificates, certbot replaces the files (or the symlinks
+// to them) without telling anyone.
+pub(crate) async fn watch(config: RustlsConfig, cert: PathBuf, key: PathBuf) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let names: HashSet<_> = [&cert, &key]
+        .iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_os_string()))
+        .collect();
+
+    let mut watcher =
+        match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| {
+                event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name().is_some_and(|name| names.contains(name)))
+            }) {
+                let _ = sender.send(());
+            }
+        }) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                println!("Failed to create certificate watcher: {error}");
+
+                return;
+            }
+        };
+
+    let folders: HashSet<_> = [&cert, &key]
+        .iter()
+        .map(|path| match path.parent() {
+            Some(folder) if !folder.as_os_str().is_empty() => folder.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+        .collect();
+
+    for folder in folders {
+        if let Err(error) = watcher.watch(&folder, RecursiveMode::NonRecursive) {
+            println!("Failed to watch {}: {error}", folder.display());
+
+            return;
+        }
+    }
+
+    while receiver.recv().await.is_some() {
+        // The certificate and key are rarely written at the same instant.
+        time::sleep(Duration::from_secs(1)).await;
+
+        while receiver.try_recv().is_ok() {}
+
+        match config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => println!("Reloaded TLS certificate from {}", cert.display()),
+            Err(error) => println!("Keeping the previous TLS certificate, {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn names_include_the_bind_address() {
+        let names = names(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+
+        assert!(names.contains(&"localhost".to_string()));
+        assert!(names.contains(&"192.168.1.20".to_string()));
+    }
+
+    #[test]
+    fn names_skip_the_unspecified_address_and_duplicates() {
+        let names = names(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let unique: HashSet<_> = names.iter().collect();
+
+        assert!(!names.contains(&"0.0.0.0".to_string()));
+        assert_eq!(unique.len(), names.len());
+
+        assert_eq!(
+            super::names(IpAddr::V4(Ipv4Addr::LOCALHOST))
+                .iter()
+                .filter(|name| *name == "127.0.0.1")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, time::Duration};
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use tokio::{sync::broadcast::error::RecvError, time};
+
+use crate::{data::app, rcon, supervisor};
+
+// The console has no notion of a response, everything the server prints
+// shortly after the command is treated as its output.
+const STDIN_RESPONSE_WINDOW: Duration = Duration::from_millis(750);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Transport {
+    Rcon,
+    Stdin,
+}
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Unavailable,
+    Rcon(rcon::Error),
+    Stdin(supervisor::Error),
+}
+
+impl Error {
+    pub(crate) fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Unavailable | Error::Rcon(_) | Error::Stdin(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unavailable => write!(
+                f,
+                "no command channel available: rcon is disabled and the server is not running under the panel"
+            ),
+            Error::Rcon(error) => write!(f, "{error}"),
+            Error::Stdin(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+pub(crate) async fn select(state: &app::State) -> Option<Transport> {
+    if state.properties.read().await.rcon.enabled {
+        Some(Transport::Rcon)
+    } else if state.supervisor.pid().is_some() {
+        Some(Transport::Stdin)
+    } else {
+        None
+    }
+}
+
+pub(crate) async fn execute(
+    state: &app::State,
+    command: &str,
+) -> Result<(Transport, String), Error> {
+    match select(state).await {
+        Some(Transport::Rcon) => state
+            .rcon
+            .execute(command)
+            .await
+            .map(|response| (Transport::Rcon, response))
+            .map_err(Error::Rcon),
+        Some(Transport::Stdin) => {
+            let (_, mut lines) = state.supervisor.console().subscribe().await;
+
+            state.supervisor.send(command).await.map_err(Error::Stdin)?;
+
+            let mut response = vec![];
+            let deadline = time::sleep(STDIN_RESPONSE_WINDOW);
+
+            tokio::pin!(deadline);
+
+            loop {
+                tokio::select! {
+                    _ = &mut deadline => break,
+                    line = lines.recv() => match line {
+                        Ok(line) => response.push(line),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+            }
+
+            Ok((Transport::Stdin, response.join("\n")))
+        }
+        None => Err(Error::Unavailable),
+    }
+}
@@ -1,22 +1,128 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fs::Permissions,
+    io::SeekFrom,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
 
 use base64::{engine::general_purpose, Engine as _};
-use rcon_client::{AuthRequest, RCONClient, RCONConfig};
-use tokio::fs;
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
 
 use crate::{
     data::server::{CachedUser, Player},
     loaders::{self, forge::Mod},
+    parsers::properties::Document,
 };
 
-pub(crate) fn encode_password<T: Into<String>>(password: T, key: T) -> String {
-    let password = password.into();
-    let input = password.as_bytes();
-    let key = key.into();
-    let k = key.as_bytes();
-    let hmac = hmac_sha256::HMAC::mac(input, k).to_vec();
+const PASSWORD_ITERATIONS: u32 = 600_000;
+
+// Costs as much to check as a real hash and never matches, logins for users
+// that do not exist are checked against it so they take just as long.
+pub(crate) const DUMMY_HASH: &str =
+    "pbkdf2-sha256$600000$AAAAAAAAAAAAAAAAAAAAAA$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+// PBKDF2-HMAC-SHA256, stored as `pbkdf2-sha256$<iterations>$<salt>$<hash>`.
+pub(crate) fn hash_password(password: &str) -> String {
+    let salt: [u8; 16] = rand::random();
+    let hash = pbkdf2(password.as_bytes(), &salt, PASSWORD_ITERATIONS);
+
+    format!(
+        "pbkdf2-sha256${PASSWORD_ITERATIONS}${}${}",
+        general_purpose::STANDARD_NO_PAD.encode(salt),
+        general_purpose::STANDARD_NO_PAD.encode(hash)
+    )
+}
+
+pub(crate) fn verify_password(password: &str, stored: &str) -> bool {
+    let mut parts = stored.split('$');
+
+    let (Some("pbkdf2-sha256"), Some(iterations), Some(salt), Some(hash), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+
+    let (Ok(iterations), Ok(salt), Ok(hash)) = (
+        iterations.parse(),
+        general_purpose::STANDARD_NO_PAD.decode(salt),
+        general_purpose::STANDARD_NO_PAD.decode(hash),
+    ) else {
+        return false;
+    };
+
+    constant_time_eq(&pbkdf2(password.as_bytes(), &salt, iterations), &hash)
+}
+
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    // HMAC with the key pads hashed once up front, `HMAC::mac` would redo
+    // that on every one of the iterations.
+    let key = if password.len() > 64 {
+        hmac_sha256::Hash::hash(password).to_vec()
+    } else {
+        password.to_vec()
+    };
+    let mut inner_pad = [0x36; 64];
+    let mut outer_pad = [0x5c; 64];
+
+    for (index, byte) in key.iter().enumerate() {
+        inner_pad[index] ^= byte;
+        outer_pad[index] ^= byte;
+    }
+
+    let mut inner = hmac_sha256::Hash::new();
+    let mut outer = hmac_sha256::Hash::new();
+
+    inner.update(&inner_pad[..]);
+    outer.update(&outer_pad[..]);
+
+    let mac = |data: &[u8]| {
+        let mut hash = inner;
+
+        hash.update(data);
+
+        let mut result = outer;
+
+        result.update(&hash.finalize()[..]);
+        result.finalize()
+    };
+
+    // A single block is enough, the output is exactly one SHA-256 digest.
+    let mut block = salt.to_vec();
+
+    block.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut round = mac(&block);
+    let mut output = round;
+
+    for _ in 1..iterations {
+        round = mac(&round);
+
+        for (output, byte) in output.iter_mut().zip(round) {
+            *output ^= byte;
+        }
+    }
+
+    output
+}
+
+// For long and random secrets, which a plain digest is enough to store.
+pub(crate) fn digest(secret: &str) -> String {
+    hmac_sha256::Hash::hash(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
 
-    general_purpose::STANDARD.encode(hmac)
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
 }
 
 pub(crate) fn scan_folder<T: Into<String>>(path: T, files: Vec<&str>) -> bool {
@@ -27,32 +133,45 @@ pub(crate) fn scan_folder<T: Into<String>>(path: T, files: Vec<&str>) -> bool {
     exists
 }
 
-pub(crate) async fn load_mods(path: PathBuf) -> Vec<Mod> {
-    let mut folder = fs::read_dir(path.join("mods"))
-        .await
-        .expect("failed to read mods folder");
+// Jars that cannot be read, like ones still being copied, are reported and
+// left out. No mods folder just means no mods.
+pub(crate) async fn load_mods(path: PathBuf) -> std::io::Result<Vec<Mod>> {
+    let mut folder = match fs::read_dir(path.join("mods")).await {
+        Ok(folder) => folder,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error),
+    };
     let mut entries = Vec::new();
 
-    while let Some(entry) = folder.next_entry().await.expect("failed to get next entry") {
-        println!("Loading mod: {:#?}", entry.file_name());
+    while let Some(entry) = folder.next_entry().await? {
+        let path = entry.path();
 
-        let mut entry = loaders::forge::load_mod_by_path(entry.path()).await;
+        // Forge skips anything else, like `.jar.disabled`.
+        if path.extension().and_then(|extension| extension.to_str()) != Some("jar") {
+            continue;
+        }
+
+        println!("Loading mod: {:#?}", entry.file_name());
 
-        entries.append(&mut entry);
+        match loaders::forge::load_mod_by_path(path).await {
+            Ok(mut mods) => entries.append(&mut mods),
+            Err(error) => println!("Skipping mod {:#?}: {error}", entry.file_name()),
+        }
     }
 
-    entries
+    Ok(entries)
+}
+
+pub(crate) async fn load_user_cache(path: &Path) -> std::io::Result<Vec<CachedUser>> {
+    let data = fs::read(path.join("usercache.json")).await?;
+
+    Ok(serde_json::from_slice(&data)?)
 }
 
-pub(crate) async fn get_players(path: PathBuf, online_uuids: Vec<String>) -> Vec<Player> {
-    let cache_data = fs::read(path.join("usercache.json"))
-        .await
-        .expect("failed to read user cache file!");
-    let cache: Vec<CachedUser> =
-        serde_json::from_slice(&cache_data).expect("failed to deserialize user cache file!");
+pub(crate) fn get_players(cache: &[CachedUser], online_uuids: Vec<String>) -> Vec<Player> {
     let mut players = vec![];
 
-    for user in cache {
+    for user in cache.iter().cloned() {
         // let file = fs::read(path.join(format!("world/playerdata/{}.dat", user.uuid)))
         //     .await
         //     .expect("failed to read player.dat file");
@@ -81,30 +200,279 @@ pub(crate) async fn get_players(path: PathBuf, online_uuids: Vec<String>) -> Vec
     players
 }
 
-pub(crate) async fn connect_rcon<T: AsRef<str>>(
-    ip: Option<String>,
-    port: u32,
-    password: Option<T>,
-) -> Option<RCONClient> {
-    let client = RCONClient::new(RCONConfig {
-        url: format!("{}:{}", ip.unwrap_or("0.0.0.0".to_string()), port),
-        write_timeout: None,
-        read_timeout: None,
-    });
-
-    if let Ok(mut client) = client {
-        if let Some(password) = password {
-            let auth_result = client
-                .auth(AuthRequest::new(password.as_ref().to_string()))
-                .unwrap();
-
-            if !auth_result.is_success() {
-                panic!("Failed to authenticate rcon!");
+pub(crate) async fn read_tail(path: PathBuf, limit: u64) -> std::io::Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let length = file.metadata().await?.len();
+    let mut data = vec![];
+
+    if length > limit {
+        file.seek(SeekFrom::Start(length - limit)).await?;
+    }
+
+    file.read_to_end(&mut data).await?;
+
+    let content = String::from_utf8_lossy(&data).to_string();
+
+    // Drop the partial line left over from seeking into the middle of the file.
+    if length > limit {
+        if let Some((_, rest)) = content.split_once('\n') {
+            return Ok(rest.to_string());
+        }
+    }
+
+    Ok(content)
+}
+
+pub(crate) fn generate_password(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+// Turns rcon on with a fresh password unless it is already usable, returns
+// whether server.properties had to be changed.
+pub(crate) async fn provision_rcon(path: &Path) -> std::io::Result<bool> {
+    let file_path = path.join("server.properties");
+    let file = fs::read_to_string(&file_path).await?;
+    let mut document = Document::parse(file);
+
+    let enabled = document
+        .get("enable-rcon")
+        .is_some_and(|value| value.trim() == "true");
+    let has_password = document
+        .get("rcon.password")
+        .is_some_and(|value| !value.trim().is_empty());
+
+    if enabled && has_password {
+        return Ok(false);
+    }
+
+    document.set("enable-rcon", "true");
+
+    if !has_password {
+        document.set("rcon.password", generate_password(32));
+    }
+
+    if document.get("rcon.port").is_none() {
+        document.set("rcon.port", "25575");
+    }
+
+    write_atomic(&file_path, document.to_string()).await?;
+
+    Ok(true)
+}
+
+// Writes next to the file and renames it over the original, so a crash or a
+// full disk leaves either the old file or the new one, never half of one.
+pub(crate) async fn write_atomic<T: AsRef<[u8]>>(path: &Path, data: T) -> std::io::Result<()> {
+    replace(path, data.as_ref(), None).await
+}
+
+// Same as `write_atomic`, for files with secrets only the panel may read.
+pub(crate) async fn write_private<T: AsRef<[u8]>>(path: &Path, data: T) -> std::io::Result<()> {
+    replace(path, data.as_ref(), Some(0o600)).await
+}
+
+async fn replace(path: &Path, data: &[u8], mode: Option<u32>) -> std::io::Result<()> {
+    let Some(name) = path.file_name() else {
+        return Err(std::io::ErrorKind::InvalidInput.into());
+    };
+    let temporary = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+
+    let result = async {
+        // A private file is created that way, not only made so before the rename.
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode.unwrap_or(0o666))
+            .open(&temporary)
+            .await?;
+
+        file.write_all(data).await?;
+        file.sync_all().await?;
+
+        match mode {
+            Some(mode) => {
+                fs::set_permissions(&temporary, Permissions::from_mode(mode)).await?;
             }
+            None => {
+                if let Ok(metadata) = fs::metadata(path).await {
+                    fs::set_permissions(&temporary, metadata.permissions()).await?;
+                }
+            }
+        }
+
+        fs::rename(&temporary, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    // RFC 7914 section 11 and the usual PBKDF2-HMAC-SHA256 vectors, truncated to
+    // the 32 bytes stored.
+    #[test]
+    fn pbkdf2_matches_known_answers() {
+        let vectors: [(&[u8], &[u8], u32, &str); 5] = [
+            (
+                b"password",
+                b"salt",
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            ),
+            (
+                b"password",
+                b"salt",
+                2,
+                "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43",
+            ),
+            (
+                b"password",
+                b"salt",
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1",
+            ),
+            (
+                b"passwd",
+                b"salt",
+                1,
+                "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc",
+            ),
+        ];
+
+        for (password, salt, iterations, expected) in vectors {
+            assert_eq!(hex(&pbkdf2(password, salt, iterations)), expected);
         }
+    }
 
-        return Some(client);
+    #[test]
+    fn pbkdf2_hashes_long_passwords_first() {
+        assert_eq!(
+            hex(&pbkdf2(&[b'x'; 100], b"NaCl", 80)),
+            "3fa15be9147b2ae199295f50157b226c23fe02134d4b19ba7b66268519bbf7ea"
+        );
     }
 
-    None
+    #[test]
+    fn hashed_passwords_verify() {
+        let stored = hash_password("correct horse");
+
+        assert!(stored.starts_with("pbkdf2-sha256$600000$"));
+        assert!(verify_password("correct horse", &stored));
+        assert!(!verify_password("correct horse ", &stored));
+    }
+
+    #[test]
+    fn malformed_hashes_never_verify() {
+        let salt = general_purpose::STANDARD_NO_PAD.encode(b"salt");
+        let hash = general_purpose::STANDARD_NO_PAD.encode(pbkdf2(b"password", b"salt", 1));
+
+        assert!(verify_password(
+            "password",
+            &format!("pbkdf2-sha256$1${salt}${hash}")
+        ));
+
+        for stored in [
+            format!("pbkdf2-sha512$1${salt}${hash}"),
+            format!("pbkdf2-sha256$one${salt}${hash}"),
+            format!("pbkdf2-sha256$1${salt}"),
+            format!("pbkdf2-sha256$1${salt}${hash}$extra"),
+            format!("pbkdf2-sha256$1$!!!${hash}"),
+            "password".to_string(),
+        ] {
+            assert!(!verify_password("password", &stored), "{stored}");
+        }
+    }
+
+    #[tokio::test]
+    async fn write_atomic_replaces_the_file_and_cleans_up() {
+        let folder = std::env::temp_dir().join(format!("habwa-write-{}", std::process::id()));
+        let path = folder.join("server.properties");
+
+        fs::create_dir_all(&folder).await.expect("create folder");
+        fs::write(&path, "old=1\n").await.expect("write file");
+
+        write_atomic(&path, "new=2\n").await.expect("replace file");
+
+        assert_eq!(
+            fs::read_to_string(&path).await.expect("read file"),
+            "new=2\n"
+        );
+
+        let mut names = vec![];
+        let mut entries = fs::read_dir(&folder).await.expect("read folder");
+
+        while let Some(entry) = entries.next_entry().await.expect("read entry") {
+            names.push(entry.file_name());
+        }
+
+        assert_eq!(names, vec!["server.properties"]);
+
+        fs::remove_dir_all(&folder).await.expect("remove folder");
+    }
+
+    #[tokio::test]
+    async fn write_private_is_owner_only() {
+        let path = std::env::temp_dir().join(format!("habwa-private-{}", std::process::id()));
+
+        fs::write(&path, "old").await.expect("write file");
+        fs::set_permissions(&path, Permissions::from_mode(0o644))
+            .await
+            .expect("open up file");
+
+        write_private(&path, "secret").await.expect("replace file");
+
+        let metadata = fs::metadata(&path).await.expect("read metadata");
+
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(
+            fs::read_to_string(&path).await.expect("read file"),
+            "secret"
+        );
+
+        fs::remove_file(&path).await.expect("remove file");
+    }
+
+    #[test]
+    fn dummy_hash_costs_as_much_as_a_real_one() {
+        let parts: Vec<&str> = DUMMY_HASH.split('$').collect();
+
+        assert_eq!(parts[0], "pbkdf2-sha256");
+        assert_eq!(parts[1], PASSWORD_ITERATIONS.to_string());
+        assert_eq!(
+            general_purpose::STANDARD_NO_PAD
+                .decode(parts[2])
+                .unwrap()
+                .len(),
+            16
+        );
+        assert_eq!(
+            general_purpose::STANDARD_NO_PAD
+                .decode(parts[3])
+                .unwrap()
+                .len(),
+            32
+        );
+    }
 }
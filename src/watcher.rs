@@ -0,0 +1,225 @@
+use std::{collections::HashSet, path::Path, sync::Arc, time::Duration};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tokio::{fs, sync::mpsc, time};
+
+use crate::{data::app, utils};
+
+// What was reloaded, sent to everyone subscribed to `app::State::changes`.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(tag = "changed", rename_all = "snake_case")]
+pub(crate) enum Change {
+    Properties,
+    UserCache,
+    Mods,
+}
+
+impl Change {
+    fn of(server_path: &Path, path: &Path) -> Option<Change> {
+        let mods = server_path.join("mods");
+
+        // The folder itself too, it may have been created or replaced.
+        if path == mods || path.parent() == Some(&mods) {
+            return Some(Change::Mods);
+        }
+
+        if path.parent() != Some(server_path) {
+            return None;
+        }
+
+        match path.file_name()?.to_str()? {
+            "server.properties" => Some(Change::Properties),
+            "usercache.json" => Some(Change::UserCache),
+            _ => None,
+        }
+    }
+}
+
+const DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+// Picks up changes made behind the panel's back, like editing the properties
+// by hand or copying jars into `mods` over SFTP.
+pub(crate) async fn watch(state: Arc<app::State>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    // Events name paths the way they were watched, some platforms resolve them.
+    let root = fs::canonicalize(&state.path)
+        .await
+        .unwrap_or_else(|_| state.path.clone());
+    let server_path = root.clone();
+
+    let mut watcher =
+        match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+
+            for change in event
+                .paths
+                .iter()
+                .filter_map(|path| Change::of(&server_path, path))
+            {
+                let _ = sender.send(change);
+            }
+        }) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                println!("Failed to create server folder watcher: {error}");
+
+                return;
+            }
+        };
+
+    if let Err(error) = watcher.watch(&root, RecursiveMode::NonRecursive) {
+        println!("Failed to watch server folder: {error}");
+
+        return;
+    }
+
+    watch_mods(&mut watcher, &root).await;
+
+    while let Some(change) = receiver.recv().await {
+        let mut changes = HashSet::from([change]);
+        let deadline = time::Instant::now() + MAX_DELAY;
+
+        // Uploads arrive in many writes, wait until they have been quiet for
+        // a while. A file written to without pause still gets picked up.
+        loop {
+            let wait = DELAY.min(deadline.saturating_duration_since(time::Instant::now()));
+
+            match time::timeout(wait, receiver.recv()).await {
+                Ok(Some(change)) => {
+                    changes.insert(change);
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        if changes.contains(&Change::Mods) {
+            watch_mods(&mut watcher, &root).await;
+        }
+
+        for change in changes {
+            if reload(&state, change).await {
+                // Nobody listening is not an error.
+                let _ = state.changes.send(change);
+            }
+        }
+    }
+}
+
+// Watched again whenever something happens to it, the folder may not have
+// existed before or may have been replaced, which ends the old watch.
+async fn watch_mods(watcher: &mut RecommendedWatcher, root: &Path) {
+    let path = root.join("mods");
+    let _ = watcher.unwatch(&path);
+
+    if !fs::metadata(&path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        return;
+    }
+
+    if let Err(error) = watcher.watch(&path, RecursiveMode::NonRecursive) {
+        println!("Failed to watch mods folder: {error}");
+    }
+}
+
+async fn reload(state: &Arc<app::State>, change: Change) -> bool {
+    match change {
+        Change::Properties => {
+            let _lock = state.properties_file.lock().await;
+
+            if let Err(error) = state.reload_properties().await {
+                println!("Keeping the previous server.properties, {error}");
+
+                return false;
+            }
+        }
+        Change::UserCache => match utils::load_user_cache(&state.path).await {
+            Ok(cache) => *state.user_cache.write().await = cache,
+            Err(error) => {
+                println!("Keeping the previous usercache.json, {error}");
+
+                return false;
+            }
+        },
+        Change::Mods => match utils::load_mods(state.path.clone()).await {
+            Ok(mods) => *state.mods.lock().await = mods,
+            Err(error) => {
+                println!("Keeping the previous mods, failed to read the mods folder: {error}");
+
+                return false;
+            }
+        },
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn files_in_the_server_folder() {
+        let root = PathBuf::from("/srv/minecraft");
+
+        assert_eq!(
+            Change::of(&root, &root.join("server.properties")),
+            Some(Change::Properties)
+        );
+        assert_eq!(
+            Change::of(&root, &root.join("usercache.json")),
+            Some(Change::UserCache)
+        );
+        assert_eq!(Change::of(&root, &root.join("ops.json")), None);
+        assert_eq!(Change::of(&root, &root.join("logs")), None);
+        // What the panel writes before renaming it over the real file.
+        assert_eq!(
+            Change::of(&root, &root.join(".server.properties.tmp")),
+            None
+        );
+    }
+
+    #[test]
+    fn only_the_top_level_files_count() {
+        let root = PathBuf::from("/srv/minecraft");
+
+        assert_eq!(
+            Change::of(&root, &root.join("world/server.properties")),
+            None
+        );
+        assert_eq!(
+            Change::of(&root, &root.join("mods/usercache.json")),
+            Some(Change::Mods)
+        );
+        assert_eq!(Change::of(&root, Path::new("/srv/server.properties")), None);
+        assert_eq!(
+            Change::of(&root, Path::new("/srv/minecraft2/server.properties")),
+            None
+        );
+    }
+
+    #[test]
+    fn mods() {
+        let root = PathBuf::from("/srv/minecraft");
+
+        assert_eq!(
+            Change::of(&root, &root.join("mods/jei.jar")),
+            Some(Change::Mods)
+        );
+        assert_eq!(
+            Change::of(&root, &root.join("mods/jei.jar.part")),
+            Some(Change::Mods)
+        );
+        assert_eq!(Change::of(&root, &root.join("mods")), Some(Change::Mods));
+        // Mods only load from the folder itself.
+        assert_eq!(Change::of(&root, &root.join("mods/config/jei.toml")), None);
+        assert_eq!(Change::of(&root, &root.join("modsx/jei.jar")), None);
+    }
+}